@@ -0,0 +1,168 @@
+//! Stand-in for a go-livepeer CLI web server, for trying out `EXECUTOR=node` locally.
+//!
+//! Serves the endpoints funds_transfer uses, with the request shape of the real node:
+//! `GET /status`, `GET /ethAddr`, `POST /reward`, and `POST /withdrawFees` with a single
+//! `amount` form field (the node always withdraws to its own account). Like the real node it
+//! has no `/transferBond`. Each request is printed; nothing is signed or sent.
+//!
+//! ```bash
+//! cargo run --example node_cli_stub
+//! EXECUTOR=node NODE_CLI_URL=http://127.0.0.1:7935 cargo run
+//! ```
+//!
+//! Env:
+//! - `STUB_ADDR` listen address (default `127.0.0.1:7935`)
+//! - `STUB_ETH_ADDR` address returned by `/ethAddr`
+//! - `STUB_FAIL` make one path fail, e.g. `/reward=500:execution reverted`
+//! - `STUB_DELAY_SECS` delay before answering POSTs, like a node waiting for a receipt
+
+use std::{
+    env,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+const DEFAULT_ETH_ADDR: &str = "0x193e8407c04dd802849fc3f24156b8eed6635819";
+
+#[derive(Clone, Debug)]
+struct Stub {
+    eth_addr: String,
+    /// `path=code:message`
+    fail: Option<String>,
+    delay: Duration,
+}
+
+impl Stub {
+    fn from_env() -> Self {
+        Self {
+            eth_addr: env::var("STUB_ETH_ADDR").unwrap_or_else(|_| DEFAULT_ETH_ADDR.into()),
+            fail: env::var("STUB_FAIL").ok(),
+            delay: Duration::from_secs(
+                env::var("STUB_DELAY_SECS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0),
+            ),
+        }
+    }
+}
+
+impl Default for Stub {
+    fn default() -> Self {
+        Self {
+            eth_addr: DEFAULT_ETH_ADDR.into(),
+            fail: None,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let addr = env::var("STUB_ADDR").unwrap_or_else(|_| "127.0.0.1:7935".into());
+    let listener = TcpListener::bind(&addr)?;
+    println!("node cli stub listening on http://{addr}");
+    serve(listener, Stub::from_env())
+}
+
+/// Answers connections on `listener` until it fails.
+fn serve(listener: TcpListener, stub: Stub) -> std::io::Result<()> {
+    let stub = Arc::new(stub);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let stub = stub.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &stub) {
+                eprintln!("connection error: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, stub: &Stub) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body).into_owned();
+    println!("{method} {path} {body}");
+
+    let (status, body) = respond(stub, &method, &path, &body);
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn respond(stub: &Stub, method: &str, path: &str, body: &str) -> (String, String) {
+    if let Some(fail) = &stub.fail
+        && let Some((fail_path, rest)) = fail.split_once('=')
+        && fail_path == path
+    {
+        let (code, msg) = rest.split_once(':').unwrap_or((rest, "stub failure"));
+        return (format!("{code} Error"), msg.to_owned());
+    }
+
+    match (method, path) {
+        ("GET", "/status") => (
+            "200 OK".into(),
+            r#"{"Version":"stub","Manifests":{},"OrchestratorPool":[]}"#.into(),
+        ),
+        ("GET", "/ethAddr") => ("200 OK".into(), stub.eth_addr.clone()),
+        ("POST", "/reward") => {
+            thread::sleep(stub.delay);
+            ("200 OK".into(), String::new())
+        }
+        ("POST", "/withdrawFees") => {
+            let form: Vec<(&str, &str)> = body
+                .split('&')
+                .filter(|p| !p.is_empty())
+                .map(|p| p.split_once('=').unwrap_or((p, "")))
+                .collect();
+            // The real node ignores anything but `amount`; a receiver sent here would be lost.
+            if let Some((name, _)) = form.iter().find(|(name, _)| *name != "amount") {
+                return (
+                    "400 Bad Request".into(),
+                    format!("unexpected form param: {name}"),
+                );
+            }
+            match form.iter().find(|(name, _)| *name == "amount") {
+                None => (
+                    "400 Bad Request".into(),
+                    "missing form param: amount".into(),
+                ),
+                Some((_, v)) if v.parse::<u128>().is_err() => {
+                    ("400 Bad Request".into(), format!("invalid amount: {v}"))
+                }
+                Some(_) => {
+                    thread::sleep(stub.delay);
+                    ("200 OK".into(), String::new())
+                }
+            }
+        }
+        _ => ("404 Not Found".into(), "404 page not found".into()),
+    }
+}
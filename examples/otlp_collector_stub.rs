@@ -0,0 +1,165 @@
+//! Stand-in for an OpenTelemetry collector, for trying out OTLP trace export locally.
+//!
+//! Accepts OTLP/HTTP JSON on `POST /v1/traces` and prints each span as an indented tree per
+//! trace, with its duration, attributes and error status. Nothing is stored.
+//!
+//! ```bash
+//! cargo run --example otlp_collector_stub
+//! OTEL_EXPORTER_OTLP_ENDPOINT=http://127.0.0.1:4318 cargo run
+//! ```
+//!
+//! Env:
+//! - `STUB_ADDR` listen address (default `127.0.0.1:4318`)
+//! - `STUB_STATUS` answer every export with this status instead of 200, e.g. `503`
+
+use std::{
+    collections::HashMap,
+    env,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use serde_json::Value;
+
+fn main() -> std::io::Result<()> {
+    let addr = env::var("STUB_ADDR").unwrap_or_else(|_| "127.0.0.1:4318".into());
+    let listener = TcpListener::bind(&addr)?;
+    println!("otlp collector stub listening on http://{addr}/v1/traces");
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            if let Err(e) = handle(stream) {
+                eprintln!("connection error: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let status = match (method.as_str(), path.as_str()) {
+        ("POST", "/v1/traces") => match serde_json::from_slice::<Value>(&body) {
+            Ok(req) => {
+                print_spans(&req);
+                env::var("STUB_STATUS").unwrap_or_else(|_| "200".into())
+            }
+            Err(e) => {
+                println!("bad export body: {e}");
+                "400".into()
+            }
+        },
+        _ => "404".into(),
+    };
+
+    let reply = "{}";
+    write!(
+        stream,
+        "HTTP/1.1 {status} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{reply}",
+        reply.len()
+    )?;
+    stream.flush()
+}
+
+fn print_spans(req: &Value) {
+    let spans: Vec<&Value> = req["resourceSpans"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|rs| rs["scopeSpans"].as_array().into_iter().flatten())
+        .flat_map(|ss| ss["spans"].as_array().into_iter().flatten())
+        .collect();
+    println!("export: spans={}", spans.len());
+
+    // Children are printed under their parent when both are in this batch.
+    let ids: Vec<&str> = spans.iter().filter_map(|s| s["spanId"].as_str()).collect();
+    let mut children: HashMap<&str, Vec<&Value>> = HashMap::new();
+    let mut roots = Vec::new();
+    for span in &spans {
+        match span["parentSpanId"].as_str() {
+            Some(parent) if ids.contains(&parent) => children.entry(parent).or_default().push(span),
+            _ => roots.push(*span),
+        }
+    }
+    for root in roots {
+        print_tree(root, &children, 1);
+    }
+}
+
+fn print_tree(span: &Value, children: &HashMap<&str, Vec<&Value>>, depth: usize) {
+    let nanos = |k: &str| {
+        span[k]
+            .as_str()
+            .and_then(|s| s.parse::<u128>().ok())
+            .unwrap_or_default()
+    };
+    let ms = nanos("endTimeUnixNano").saturating_sub(nanos("startTimeUnixNano")) as f64 / 1e6;
+
+    let attrs: Vec<String> = span["attributes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|a| {
+            let v = &a["value"];
+            let v = ["stringValue", "intValue", "boolValue", "doubleValue"]
+                .iter()
+                .find_map(|k| v.get(*k))
+                .map(|v| {
+                    v.as_str()
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| v.to_string())
+                })
+                .unwrap_or_default();
+            format!("{}={}", a["key"].as_str().unwrap_or("?"), v)
+        })
+        .collect();
+    let error = match span["status"]["code"].as_u64() {
+        Some(2) => format!(
+            " ERROR({})",
+            span["status"]["message"].as_str().unwrap_or("")
+        ),
+        _ => String::new(),
+    };
+
+    println!(
+        "{:indent$}{} {:.1}ms trace={} {}{}",
+        "",
+        span["name"].as_str().unwrap_or("?"),
+        ms,
+        span["traceId"].as_str().unwrap_or("?"),
+        attrs.join(" "),
+        error,
+        indent = depth * 2
+    );
+    if let Some(id) = span["spanId"].as_str()
+        && let Some(kids) = children.get(id)
+    {
+        for kid in kids {
+            print_tree(kid, children, depth + 1);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Stand-in webhook receiver, for trying out notifications locally.
+//!
+//! Accepts `POST` on any path and prints the JSON body, whatever the format (generic, Slack or
+//! Discord). Nothing is stored.
+//!
+//! ```bash
+//! cargo run --example webhook_stub
+//! NOTIFY_WEBHOOK_URLS="http://127.0.0.1:9000/generic,http://127.0.0.1:9000/slack;format=slack" cargo run
+//! ```
+//!
+//! Env:
+//! - `STUB_ADDR` listen address (default `127.0.0.1:9000`)
+//! - `STUB_STATUS` answer every request with this status instead of 200, e.g. `429`
+
+use std::{
+    env,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use serde_json::Value;
+
+fn main() -> std::io::Result<()> {
+    let addr = env::var("STUB_ADDR").unwrap_or_else(|_| "127.0.0.1:9000".into());
+    let listener = TcpListener::bind(&addr)?;
+    println!("webhook stub listening on http://{addr}");
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            if let Err(e) = handle(stream) {
+                eprintln!("connection error: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let status = if method == "POST" {
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(v) => println!("{method} {path} {v}"),
+            Err(e) => println!("{method} {path} bad json body: {e}"),
+        }
+        env::var("STUB_STATUS").unwrap_or_else(|_| "200".into())
+    } else {
+        "405".into()
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status} Stub\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok"
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,111 @@
+//! Backup keeper mode.
+//!
+//! A backup instance watches the same orchestrator as the primary but only acts once the
+//! primary has visibly missed something on chain: `reward()` when `lastRewardRound` is still
+//! behind `BACKUP_REWARD_DELAY_BLOCKS` after the round was first seen initialized,
+//! transferBond/withdrawFees when they are still due `BACKUP_LOCKED_DELAY_BLOCKS` into the lock
+//! window. Nothing is shared between the two instances.
+
+use ethers::types::U256;
+use tracing::{debug, info};
+
+use crate::reads::ChainReads;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BackupPolicy {
+    /// L1 blocks after the round is initialized before reward() is taken over.
+    pub reward_delay_blocks: u64,
+    /// L1 blocks after the lock window opens before locked-round actions are taken over.
+    pub locked_delay_blocks: u64,
+}
+
+/// [`BackupPolicy`] plus what the backup has seen of the current round.
+#[derive(Debug)]
+pub struct BackupKeeper {
+    policy: BackupPolicy,
+    /// `(round, L1 block)` the round was first seen initialized at. reward() can't be called
+    /// before that, so the primary's grace period starts there, not at the round start block.
+    initialized_at: Option<(U256, U256)>,
+    /// Actions already logged as taken over in `.0`.
+    taken_over: (U256, Vec<String>),
+}
+
+impl BackupKeeper {
+    pub fn new(policy: BackupPolicy) -> Self {
+        Self {
+            policy,
+            initialized_at: None,
+            taken_over: (U256::zero(), Vec::new()),
+        }
+    }
+
+    /// Call once per loop with fresh reads. A round already initialized when the backup
+    /// starts counts from the first block seen.
+    pub fn observe(&mut self, reads: &ChainReads) {
+        let round = reads.state.round;
+        if let Some(schedule) = &reads.schedule
+            && reads.state.initialized
+            && self.initialized_at.is_none_or(|(r, _)| r != round)
+        {
+            debug!(
+                "backup: round {} seen initialized at block {}",
+                round, schedule.block_num
+            );
+            self.initialized_at = Some((round, schedule.block_num));
+        }
+    }
+
+    /// Whether a due reward() is still left to the primary.
+    pub fn defer_reward(&mut self, reads: &ChainReads) -> bool {
+        let Some(schedule) = &reads.schedule else {
+            return self.unscheduled("reward");
+        };
+        let since = match self.initialized_at {
+            Some((r, block)) if r == reads.state.round => block,
+            _ => schedule.block_num,
+        };
+        let from = since + U256::from(self.policy.reward_delay_blocks);
+        self.defer("reward", from, schedule.block_num, reads.state.round)
+    }
+
+    /// Whether a due transferBond/withdrawFees is still left to the primary.
+    pub fn defer_locked(&mut self, action: &str, reads: &ChainReads) -> bool {
+        let Some(schedule) = &reads.schedule else {
+            return self.unscheduled(action);
+        };
+        let from = schedule.lock_start_block + U256::from(self.policy.locked_delay_blocks);
+        self.defer(action, from, schedule.block_num, reads.state.round)
+    }
+
+    /// The delays are counted in L1 blocks, so without the schedule nothing is taken over.
+    fn unscheduled(&self, action: &str) -> bool {
+        debug!("backup: {action} left to primary: round schedule was not read");
+        true
+    }
+
+    fn defer(&mut self, action: &str, takeover_block: U256, block: U256, round: U256) -> bool {
+        if block < takeover_block {
+            debug!(
+                "backup: {action} left to primary: block={} takeover_block={} blocks_left={}",
+                block,
+                takeover_block,
+                takeover_block - block
+            );
+            return true;
+        }
+
+        if self.taken_over.0 != round {
+            self.taken_over = (round, Vec::new());
+        }
+        if self.taken_over.1.iter().any(|a| a == action) {
+            debug!("backup: {action} still taken over: round={round} block={block}");
+        } else {
+            info!(
+                "backup: primary missed {action}, taking over: round={} block={} takeover_block={}",
+                round, block, takeover_block
+            );
+            self.taken_over.1.push(action.to_owned());
+        }
+        false
+    }
+}
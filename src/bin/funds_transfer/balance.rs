@@ -0,0 +1,226 @@
+//! Signer ETH balance guard.
+//!
+//! The signer's balance is read with the other chain values every loop and measured as a
+//! runway: how many cycles of the enabled actions (reward, transferBond, withdrawFees) it pays
+//! for at the current gas price. Below `LOW_BALANCE_WARN_CYCLES` a warning is logged and sent;
+//! below `LOW_BALANCE_CRITICAL_CYCLES` transferBond and withdrawFees are paused so that what is
+//! left goes to `reward()`, the one action whose loss can't be made up later. A gas top-up from
+//! fees (`GAS_TOPUP_TARGET_WEI`) still goes out, since it refills the signer. Gas per action is
+//! taken from the latest mined tx, or a typical Arbitrum One figure before the first one.
+
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ethers::{providers::Middleware, types::U256, utils::format_ether};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    metrics::Metrics,
+    notify::{Notification, Notifier, NotifyEvent, Severity},
+    reads::ChainReads,
+};
+
+/// eth_gasPrice is re-read at most this often.
+const GAS_PRICE_REFRESH: Duration = Duration::from_secs(600);
+/// Gas used by each action until one has been mined, with some headroom.
+const DEFAULT_GAS: [(&str, u64); 3] = [
+    ("reward", 400_000),
+    ("transferBond", 450_000),
+    ("withdrawFees", 250_000),
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct BalanceGuardConfig {
+    pub warn_cycles: u64,
+    pub critical_cycles: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Funds {
+    Ok,
+    Low,
+    /// Only `reward()` may spend.
+    Critical,
+}
+
+impl fmt::Display for Funds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Funds::Ok => "ok",
+            Funds::Low => "low",
+            Funds::Critical => "critical",
+        })
+    }
+}
+
+pub struct BalanceGuard<M> {
+    client: Arc<M>,
+    cfg: BalanceGuardConfig,
+    actions: Vec<&'static str>,
+    gas_price: Option<(U256, Instant)>,
+    level: Funds,
+    /// Round the current level was last reported in.
+    reported_round: Option<U256>,
+    metrics: Arc<Metrics>,
+    notifier: Notifier,
+}
+
+impl<M: Middleware + 'static> BalanceGuard<M> {
+    /// `actions` are the enabled actions, by their tx names.
+    pub fn new(
+        client: Arc<M>,
+        cfg: BalanceGuardConfig,
+        actions: Vec<&'static str>,
+        metrics: Arc<Metrics>,
+        notifier: Notifier,
+    ) -> Self {
+        info!(
+            "balance guard: warn_cycles={} critical_cycles={} actions={}",
+            cfg.warn_cycles,
+            cfg.critical_cycles,
+            actions.join(",")
+        );
+        Self {
+            client,
+            cfg,
+            actions,
+            gas_price: None,
+            level: Funds::Ok,
+            reported_round: None,
+            metrics,
+            notifier,
+        }
+    }
+
+    /// Rates this loop's balance. Without a balance or a gas price the previous level stands.
+    pub async fn check(&mut self, reads: &ChainReads) -> Funds {
+        let Some(balance) = reads.balance else {
+            return self.level;
+        };
+        let Some(gas_price) = self.gas_price().await else {
+            return self.level;
+        };
+        let cycle = self.cycle_cost(gas_price);
+        if cycle.is_zero() {
+            return self.level;
+        }
+        let cycles = (balance / cycle).low_u64();
+        self.metrics
+            .runway(balance.low_u128() as f64 / cycle.low_u128() as f64);
+
+        let level = if cycles < self.cfg.critical_cycles {
+            Funds::Critical
+        } else if cycles < self.cfg.warn_cycles {
+            Funds::Low
+        } else {
+            Funds::Ok
+        };
+        let round = reads.state.round;
+        let changed = level != self.level;
+        let worse = level > self.level;
+        self.level = level;
+
+        if level == Funds::Ok {
+            if changed {
+                info!(
+                    event = "balance_ok",
+                    balance_wei = %balance,
+                    runway_cycles = cycles,
+                    "signer balance back above the runway threshold"
+                );
+            }
+            return level;
+        }
+        // Once per round while short, and on every change.
+        if !changed && self.reported_round == Some(round) {
+            return level;
+        }
+        self.reported_round = Some(round);
+        let needed = cycle.saturating_mul(U256::from(self.cfg.warn_cycles));
+        if level == Funds::Critical {
+            error!(
+                event = "balance_critical",
+                balance_wei = %balance,
+                runway_cycles = cycles,
+                cycle_cost_wei = %cycle,
+                "signer balance critical: keeping the rest for reward() (transferBond and fee payouts paused)"
+            );
+        } else {
+            warn!(
+                event = "balance_low",
+                balance_wei = %balance,
+                runway_cycles = cycles,
+                cycle_cost_wei = %cycle,
+                "signer balance low: top up to about {}",
+                eth(needed)
+            );
+        }
+        if worse {
+            self.notifier.notify(
+                Notification::new(NotifyEvent::LowBalance, format!("signer balance {level}"))
+                    .severity(match level {
+                        Funds::Critical => Severity::Critical,
+                        _ => Severity::Warning,
+                    })
+                    .field("balance", eth(balance))
+                    .field("runway_cycles", cycles)
+                    .field("recommended", eth(needed)),
+            );
+        }
+        level
+    }
+
+    /// One round of the enabled actions at `gas_price`.
+    fn cycle_cost(&self, gas_price: U256) -> U256 {
+        self.actions
+            .iter()
+            .map(|action| {
+                let gas = self.metrics.last_gas_used(action).unwrap_or_else(|| {
+                    DEFAULT_GAS
+                        .iter()
+                        .find(|(a, _)| a == action)
+                        .map_or(U256::zero(), |(_, g)| U256::from(*g))
+                });
+                gas.saturating_mul(gas_price)
+            })
+            .fold(U256::zero(), U256::saturating_add)
+    }
+
+    async fn gas_price(&mut self) -> Option<U256> {
+        if let Some((price, at)) = self.gas_price
+            && at.elapsed() < GAS_PRICE_REFRESH
+        {
+            return Some(price);
+        }
+        match self.client.get_gas_price().await {
+            Ok(price) => {
+                debug!("balance guard: gas_price_wei={price}");
+                self.gas_price = Some((price, Instant::now()));
+                Some(price)
+            }
+            Err(e) => {
+                warn!("gas price read failed: {e}; keeping the last runway estimate");
+                self.gas_price.map(|(price, _)| price)
+            }
+        }
+    }
+}
+
+/// Wei as ETH with at most 6 decimals.
+fn eth(wei: U256) -> String {
+    let s = format_ether(wei);
+    match s.split_once('.') {
+        Some((int, frac)) => {
+            let frac = frac[..frac.len().min(6)].trim_end_matches('0');
+            if frac.is_empty() {
+                format!("{int} ETH")
+            } else {
+                format!("{int}.{frac} ETH")
+            }
+        }
+        None => format!("{s} ETH"),
+    }
+}
@@ -0,0 +1,165 @@
+//! Event-driven wakeups over WebSocket.
+//!
+//! When `WS_RPC_URL` is set, a background task subscribes to RoundsManager `NewRound` logs and
+//! to BondingManager `Reward` / `TransferBond` / `WithdrawFees` logs for the orchestrator, and
+//! wakes the main loop as soon as one arrives. The polling loop keeps running underneath, so a
+//! dropped socket only means we fall back to the scheduler until the subscription is back.
+//! BondingManager events from txs this process didn't send (e.g. go-livepeer using the same
+//! key) are logged as external.
+
+use std::{sync::Arc, time::Duration};
+
+use ethers::{
+    contract::EthEvent,
+    providers::{ConnectionDetails, Middleware, Provider, StreamExt, Ws},
+    types::{Address, Filter, H256, Log},
+};
+use tokio::{sync::Notify, time::sleep};
+use tracing::{debug, info, warn};
+
+// BondingManager declares two `WithdrawFees` overloads (with and without recipient/amount);
+// abigen numbers them, and we listen for both.
+use crate::{
+    NewRoundFilter, RewardFilter, TransferBondFilter, WithdrawFees1Filter, WithdrawFees2Filter,
+    rpc::{EndpointSpec, redact_url},
+    tx::OwnTxs,
+};
+
+const RESUBSCRIBE_BACKOFF_MIN_SECS: u64 = 1;
+const RESUBSCRIBE_BACKOFF_MAX_SECS: u64 = 60;
+
+#[derive(Clone, Debug)]
+pub struct EventSource {
+    pub ws: EndpointSpec,
+    pub rounds_manager_addr: Address,
+    pub bonding_manager_addr: Address,
+    pub orchestrator: Address,
+    pub own_txs: OwnTxs,
+}
+
+/// Spawn the subscription task. It runs for the lifetime of the process and never returns an
+/// error; connection failures are logged and retried with backoff.
+pub fn spawn_listener(src: EventSource, wake: Arc<Notify>) {
+    tokio::spawn(async move {
+        let mut backoff = RESUBSCRIBE_BACKOFF_MIN_SECS;
+        loop {
+            match listen_once(&src, &wake, &mut backoff).await {
+                Ok(()) => {
+                    warn!(
+                        "event subscription ended; resubscribing in {backoff}s (polling continues)"
+                    );
+                }
+                Err(e) => {
+                    warn!(
+                        "event subscription failed: {e}; resubscribing in {backoff}s (polling continues)"
+                    );
+                }
+            }
+            sleep(Duration::from_secs(backoff)).await;
+            backoff = (backoff * 2).min(RESUBSCRIBE_BACKOFF_MAX_SECS);
+        }
+    });
+}
+
+/// Returns once the subscription ends. `backoff` is reset as soon as both subscriptions are
+/// established so a long-lived connection that eventually drops reconnects quickly.
+async fn listen_once(src: &EventSource, wake: &Notify, backoff: &mut u64) -> Result<(), String> {
+    let conn = ConnectionDetails::new(src.ws.url.as_str(), src.ws.auth.authorization.clone());
+    let provider = Provider::<Ws>::connect(conn).await.map_err(|e| {
+        // Connection errors can echo the URL, which may carry an API key.
+        let e = e
+            .to_string()
+            .replace(src.ws.url.as_str(), &redact_url(&src.ws.url));
+        format!(
+            "ws connect failed: endpoint={} err={e}",
+            redact_url(&src.ws.url)
+        )
+    })?;
+
+    let rounds_filter = Filter::new()
+        .address(src.rounds_manager_addr)
+        .topic0(NewRoundFilter::signature());
+
+    let orchestrator_topic = H256::from(src.orchestrator);
+    let bonding_filter = Filter::new()
+        .address(src.bonding_manager_addr)
+        .topic0(vec![
+            RewardFilter::signature(),
+            TransferBondFilter::signature(),
+            WithdrawFees1Filter::signature(),
+            WithdrawFees2Filter::signature(),
+        ])
+        .topic1(orchestrator_topic);
+
+    let mut rounds_stream = provider
+        .subscribe_logs(&rounds_filter)
+        .await
+        .map_err(|e| format!("subscribe NewRound failed: {e}"))?;
+    let mut bonding_stream = provider
+        .subscribe_logs(&bonding_filter)
+        .await
+        .map_err(|e| format!("subscribe BondingManager events failed: {e}"))?;
+
+    info!(
+        "event subscription active: endpoint={} rounds_manager={:?} bonding_manager={:?} orchestrator={:?}",
+        redact_url(&src.ws.url),
+        src.rounds_manager_addr,
+        src.bonding_manager_addr,
+        src.orchestrator
+    );
+
+    *backoff = RESUBSCRIBE_BACKOFF_MIN_SECS;
+
+    loop {
+        let log = tokio::select! {
+            l = rounds_stream.next() => l,
+            l = bonding_stream.next() => l,
+        };
+        // Either stream ending means the socket is gone.
+        let Some(log) = log else {
+            return Ok(());
+        };
+
+        let name = event_name(&log);
+        if name == "NewRound" {
+            info!(
+                "event received: {} block={:?} tx_hash={:?}",
+                name, log.block_number, log.transaction_hash
+            );
+        } else {
+            let external = log
+                .transaction_hash
+                .is_some_and(|h| !src.own_txs.contains(h));
+            info!(
+                "event received: {} block={:?} tx_hash={:?} external={}",
+                name, log.block_number, log.transaction_hash, external
+            );
+        }
+        wake.notify_one();
+    }
+}
+
+fn event_name(log: &Log) -> &'static str {
+    match log.topics.first() {
+        Some(t) if *t == NewRoundFilter::signature() => "NewRound",
+        Some(t) if *t == RewardFilter::signature() => "Reward",
+        Some(t) if *t == TransferBondFilter::signature() => "TransferBond",
+        Some(t)
+            if *t == WithdrawFees1Filter::signature() || *t == WithdrawFees2Filter::signature() =>
+        {
+            "WithdrawFees"
+        }
+        _ => {
+            debug!("unrecognized log topics: {:?}", log.topics);
+            "unknown"
+        }
+    }
+}
+
+/// Sleep for `dur`, returning early if an on-chain event woke the loop.
+pub async fn sleep_or_wake(dur: Duration, wake: &Notify) {
+    tokio::select! {
+        _ = sleep(dur) => {}
+        _ = wake.notified() => debug!("loop woken early by on-chain event"),
+    }
+}
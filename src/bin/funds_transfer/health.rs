@@ -0,0 +1,200 @@
+//! Liveness and readiness, served on `/healthz` and `/readyz` next to `/metrics`.
+//!
+//! Liveness is a loop heartbeat: every iteration records when it will next check in, and the
+//! process counts as stuck once `HEALTH_MAX_MISSED_LOOPS` of those check-ins have been missed.
+//! Readiness covers what has to be true before the loop can act: valid config, a loaded signer
+//! (the decrypted keystore, or the node's address), a reachable RPC and the expected chain ID.
+//!
+//! `funds_transfer healthcheck` probes both endpoints for a Docker HEALTHCHECK or a Kubernetes
+//! exec probe.
+
+use std::{
+    env,
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::shutdown;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub struct Health {
+    inner: Mutex<Inner>,
+    max_missed_loops: u32,
+    /// Time one iteration may spend on top of its sleep, e.g. waiting for a receipt.
+    work_allowance: Duration,
+}
+
+#[derive(Debug)]
+struct Inner {
+    last_beat: Instant,
+    next_sleep: Duration,
+    signer: bool,
+    chain_id: bool,
+    rpc: Option<bool>,
+}
+
+impl Health {
+    /// Starts the first heartbeat interval as if a loop had just slept `first_sleep`.
+    pub fn new(max_missed_loops: u32, first_sleep: Duration, work_allowance: Duration) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                last_beat: Instant::now(),
+                next_sleep: first_sleep,
+                signer: false,
+                chain_id: false,
+                rpc: None,
+            }),
+            max_missed_loops,
+            work_allowance,
+        }
+    }
+
+    fn inner(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The loop is about to sleep for `next_sleep`.
+    pub fn beat(&self, next_sleep: Duration) {
+        let mut inner = self.inner();
+        inner.last_beat = Instant::now();
+        inner.next_sleep = next_sleep;
+    }
+
+    pub fn signer_loaded(&self) {
+        self.inner().signer = true;
+    }
+
+    pub fn chain_id_verified(&self) {
+        self.inner().chain_id = true;
+    }
+
+    /// Outcome of the latest chain reads.
+    pub fn rpc_reachable(&self, ok: bool) {
+        self.inner().rpc = Some(ok);
+    }
+
+    /// `/healthz`: `Ok` while the loop keeps checking in.
+    pub fn liveness(&self) -> Result<String, String> {
+        let inner = self.inner();
+        let elapsed = inner.last_beat.elapsed();
+        let limit = (inner.next_sleep + self.work_allowance) * self.max_missed_loops;
+        let body = format!(
+            "last_loop_secs_ago={} limit_secs={}\n",
+            elapsed.as_secs(),
+            limit.as_secs()
+        );
+        if elapsed <= limit {
+            Ok(format!("ok {body}"))
+        } else {
+            Err(format!("stale {body}"))
+        }
+    }
+
+    /// `/readyz`: `Ok` once every check passes; the body lists each one.
+    pub fn readiness(&self) -> Result<String, String> {
+        let inner = self.inner();
+        let checks = [
+            // The process only gets as far as serving this with a valid config.
+            ("config", Some(true)),
+            // Still pending until done; failures here stop the process instead.
+            ("signer", inner.signer.then_some(true)),
+            ("chain_id", inner.chain_id.then_some(true)),
+            ("rpc", inner.rpc),
+        ];
+
+        let mut body = String::new();
+        let mut ready = true;
+        for (name, ok) in checks {
+            let status = match ok {
+                Some(true) => "ok",
+                Some(false) => "failing",
+                None => "pending",
+            };
+            ready &= ok == Some(true);
+            body.push_str(&format!("{name} {status}\n"));
+        }
+        if ready { Ok(body) } else { Err(body) }
+    }
+}
+
+/// `funds_transfer healthcheck [path...]`: GETs each path (default `/healthz` and `/readyz`)
+/// from the server on `METRICS_ADDR` and exits 0 only if all answer 200.
+pub fn healthcheck(paths: &[String]) -> u8 {
+    let addr = match env::var("METRICS_ADDR") {
+        Ok(raw) if !raw.trim().is_empty() => match raw.trim().parse::<SocketAddr>() {
+            Ok(addr) => addr,
+            Err(e) => {
+                println!("healthcheck: invalid METRICS_ADDR {raw:?}: {e}");
+                return shutdown::EXIT_FAILURE;
+            }
+        },
+        _ => {
+            println!("healthcheck: METRICS_ADDR is not set; nothing to probe");
+            return shutdown::EXIT_FAILURE;
+        }
+    };
+    let addr = local_addr(addr);
+
+    let default = ["/healthz".to_owned(), "/readyz".to_owned()];
+    let paths = if paths.is_empty() {
+        &default[..]
+    } else {
+        paths
+    };
+
+    let mut code = shutdown::EXIT_OK;
+    for path in paths {
+        match probe(addr, path) {
+            Ok((200, body)) => println!("{path}: {}", body.trim().replace('\n', ", ")),
+            Ok((status, body)) => {
+                println!(
+                    "{path}: status={status} {}",
+                    body.trim().replace('\n', ", ")
+                );
+                code = shutdown::EXIT_FAILURE;
+            }
+            Err(e) => {
+                println!("{path}: {addr} unreachable: {e}");
+                code = shutdown::EXIT_FAILURE;
+            }
+        }
+    }
+    code
+}
+
+/// A wildcard listen address is probed on loopback.
+fn local_addr(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), addr.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), addr.port())
+        }
+        _ => addr,
+    }
+}
+
+fn probe(addr: SocketAddr, path: &str) -> std::io::Result<(u16, String)> {
+    let mut stream = TcpStream::connect_timeout(&addr, PROBE_TIMEOUT)?;
+    stream.set_read_timeout(Some(PROBE_TIMEOUT))?;
+    stream.set_write_timeout(Some(PROBE_TIMEOUT))?;
+    write!(
+        stream,
+        "GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n"
+    )?;
+
+    let mut raw = String::new();
+    stream.read_to_string(&mut raw)?;
+    let (head, body) = raw.split_once("\r\n\r\n").unwrap_or((&raw, ""));
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| std::io::Error::other("malformed response"))?;
+    Ok((status, body.to_owned()))
+}
@@ -0,0 +1,183 @@
+//! Transcoder pool position hints for transferBond.
+//!
+//! The pool is a list sorted by total stake, highest first. transferBond moves stake off the
+//! orchestrator and onto the receiver's delegate (the orchestrator itself when the receiver is
+//! not bonded yet), and each move takes a `(prev, next)` pair for the delegate's new place in
+//! the list. The contract falls back to walking the list when a pair is wrong or zero, so the
+//! hints only save gas; a stale pool read costs gas but never fails the tx.
+//!
+//! The pool is read once per payout, at the loop's block, and each confirmed leg is applied to
+//! the copy here instead of reading it again.
+
+use ethers::{
+    providers::Middleware,
+    types::{Address, U64, U256},
+};
+
+use crate::{AppError, BondingManager};
+
+/// The transcoder pool with each transcoder's total stake, in list order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pool(Vec<(Address, U256)>);
+
+/// `(oldPrev, oldNext, newPrev, newNext)` for one transferBond.
+pub type Hints = [Address; 4];
+
+impl Pool {
+    pub fn new(transcoders: Vec<(Address, U256)>) -> Self {
+        Self(transcoders)
+    }
+
+    /// [`Pool::transfer`] to `receiver`'s delegate as of `block`: the account its bond is
+    /// added to, or `orchestrator` when it isn't bonded yet.
+    pub async fn transfer_to<M: Middleware + 'static>(
+        &self,
+        bonding: &BondingManager<M>,
+        orchestrator: Address,
+        receiver: Address,
+        amount: U256,
+        block: U64,
+    ) -> Result<(Hints, Pool), AppError> {
+        let delegate = bonding
+            .get_delegator(receiver)
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| AppError::contract("BondingManager.getDelegator()", &e))?
+            .2;
+        let delegate = if delegate.is_zero() {
+            orchestrator
+        } else {
+            delegate
+        };
+        let stake = match self.0.iter().find(|(a, _)| *a == delegate) {
+            Some((_, stake)) => *stake,
+            None => bonding
+                .transcoder_total_stake(delegate)
+                .block(block)
+                .call()
+                .await
+                .map_err(|e| AppError::contract("BondingManager.transcoderTotalStake()", &e))?,
+        };
+        Ok(self.transfer(orchestrator, delegate, stake, amount))
+    }
+
+    /// Hints for moving `amount` from `orchestrator` to `delegate`, whose total stake is
+    /// `delegate_stake` when it isn't in the pool, and the pool once the move is mined.
+    pub fn transfer(
+        &self,
+        orchestrator: Address,
+        delegate: Address,
+        delegate_stake: U256,
+        amount: U256,
+    ) -> (Hints, Pool) {
+        let mut pool = self.0.clone();
+
+        // Unbonding part of the orchestrator's own stake lowers its key; all of it takes it out.
+        let old_hint = match pool.iter().position(|(a, _)| *a == orchestrator) {
+            Some(i) => {
+                let (_, stake) = pool.remove(i);
+                let left = stake.saturating_sub(amount);
+                if left.is_zero() {
+                    (Address::zero(), Address::zero())
+                } else {
+                    let hint = position(&pool, left);
+                    insert(&mut pool, orchestrator, left);
+                    hint
+                }
+            }
+            None => (Address::zero(), Address::zero()),
+        };
+
+        // Only a delegate in the pool gets a new place in it.
+        let new_hint = match pool.iter().position(|(a, _)| *a == delegate) {
+            Some(i) => {
+                let (_, stake) = pool.remove(i);
+                let key = stake.saturating_add(amount);
+                let hint = position(&pool, key);
+                insert(&mut pool, delegate, key);
+                hint
+            }
+            None => position(&pool, delegate_stake.saturating_add(amount)),
+        };
+
+        ([old_hint.0, old_hint.1, new_hint.0, new_hint.1], Pool(pool))
+    }
+}
+
+/// Neighbours for a node keyed `key`: after every node with at least that stake.
+fn position(pool: &[(Address, U256)], key: U256) -> (Address, Address) {
+    let i = pool.iter().take_while(|(_, s)| *s >= key).count();
+    let prev = i.checked_sub(1).map_or(Address::zero(), |p| pool[p].0);
+    let next = pool.get(i).map_or(Address::zero(), |n| n.0);
+    (prev, next)
+}
+
+fn insert(pool: &mut Vec<(Address, U256)>, addr: Address, key: U256) {
+    let i = pool.iter().take_while(|(_, s)| *s >= key).count();
+    pool.insert(i, (addr, key));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn a(n: u8) -> Address {
+        Address::repeat_byte(n)
+    }
+
+    fn pool(entries: &[(u8, u64)]) -> Pool {
+        Pool::new(
+            entries
+                .iter()
+                .map(|(n, s)| (a(*n), U256::from(*s)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn position_goes_after_equal_stakes() {
+        let p = pool(&[(1, 9), (2, 5), (3, 5), (4, 1)]).0;
+        assert_eq!(position(&p, U256::from(10)), (Address::zero(), a(1)));
+        assert_eq!(position(&p, U256::from(5)), (a(3), a(4)));
+        assert_eq!(position(&p, U256::zero()), (a(4), Address::zero()));
+        assert_eq!(
+            position(&[], U256::one()),
+            (Address::zero(), Address::zero())
+        );
+
+        let mut p = p;
+        insert(&mut p, a(5), U256::from(5));
+        assert_eq!(p[3], (a(5), U256::from(5)));
+    }
+
+    #[test]
+    fn transfer_to_another_transcoder() {
+        // Orchestrator 2 moves 3 to a receiver delegated to 4.
+        let before = pool(&[(1, 9), (2, 5), (3, 4), (4, 1)]);
+        let (hints, after) = before.transfer(a(2), a(4), U256::zero(), U256::from(3));
+        assert_eq!(hints, [a(3), a(4), a(3), a(2)]);
+        assert_eq!(after, pool(&[(1, 9), (3, 4), (4, 4), (2, 2)]));
+
+        // The next leg starts from the updated pool.
+        let (hints, after) = after.transfer(a(2), a(4), U256::zero(), U256::from(2));
+        assert_eq!(hints, [Address::zero(), Address::zero(), a(1), a(3)]);
+        assert_eq!(after, pool(&[(1, 9), (4, 6), (3, 4)]));
+    }
+
+    #[test]
+    fn transfer_to_an_unbonded_receiver_keeps_the_orchestrator_in_place() {
+        let before = pool(&[(1, 9), (2, 5), (3, 4)]);
+        let (hints, after) = before.transfer(a(2), a(2), U256::from(5), U256::from(3));
+        assert_eq!(hints, [a(3), Address::zero(), a(1), a(3)]);
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn delegate_outside_the_pool_stays_out() {
+        let before = pool(&[(1, 9), (2, 5)]);
+        let (hints, after) = before.transfer(a(2), a(7), U256::from(8), U256::from(1));
+        assert_eq!(hints, [a(1), Address::zero(), a(1), a(2)]);
+        assert_eq!(after, pool(&[(1, 9), (2, 4)]));
+    }
+}
@@ -0,0 +1,136 @@
+//! Minimal HTTP server on `METRICS_ADDR`: `/metrics`, `/healthz` and `/readyz`.
+//!
+//! Only plain `GET` requests without a body are expected, so this reads the request line,
+//! skips the headers and answers with `Connection: close`.
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tracing::{debug, info, warn};
+
+use crate::{AppError, health::Health, metrics::Metrics, rpc::FailoverClient};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest request head accepted before the connection is dropped.
+const MAX_HEAD_BYTES: usize = 8 * 1024;
+
+#[derive(Clone)]
+pub struct HttpState {
+    pub metrics: Arc<Metrics>,
+    pub health: Arc<Health>,
+    pub rpc: Arc<FailoverClient>,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+/// Binds `addr` and serves in the background; a bind failure is a config error.
+pub async fn serve(addr: SocketAddr, state: HttpState) -> Result<(), AppError> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| AppError::BadEnv("METRICS_ADDR", format!("cannot listen on {addr}: {e}")))?;
+    info!(
+        "http server listening: addr={} paths=/metrics,/healthz,/readyz",
+        addr
+    );
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = timeout(REQUEST_TIMEOUT, handle(stream, &state)).await {
+                            debug!("http request timed out: peer={} err={e}", peer);
+                        }
+                    });
+                }
+                Err(e) => {
+                    warn!("http server accept failed: {e}");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn handle(stream: TcpStream, state: &HttpState) {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await.is_err() {
+        return;
+    }
+    let mut head_len = request_line.len();
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => head_len += n,
+        }
+        if line == "\r\n" || line == "\n" {
+            break;
+        }
+        if head_len > MAX_HEAD_BYTES {
+            return;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default();
+    let resp = route(method, path, state);
+
+    let mut stream = reader.into_inner();
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        resp.status,
+        resp.content_type,
+        resp.body.len()
+    );
+    let _ = stream.write_all(head.as_bytes()).await;
+    if method != "HEAD" {
+        let _ = stream.write_all(resp.body.as_bytes()).await;
+    }
+    let _ = stream.shutdown().await;
+}
+
+fn route(method: &str, path: &str, state: &HttpState) -> Response {
+    if method != "GET" && method != "HEAD" {
+        return text("405 Method Not Allowed", "method not allowed\n");
+    }
+    match path {
+        "/metrics" => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4; charset=utf-8",
+            body: state.metrics.render(&state.rpc.health()),
+        },
+        "/healthz" => check(state.health.liveness()),
+        "/readyz" => check(state.health.readiness()),
+        _ => text("404 Not Found", "not found\n"),
+    }
+}
+
+fn check(res: Result<String, String>) -> Response {
+    match res {
+        Ok(body) => text("200 OK", &body),
+        Err(body) => text("503 Service Unavailable", &body),
+    }
+}
+
+fn text(status: &'static str, body: &str) -> Response {
+    Response {
+        status,
+        content_type: "text/plain; charset=utf-8",
+        body: body.to_owned(),
+    }
+}
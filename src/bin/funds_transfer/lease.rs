@@ -0,0 +1,233 @@
+//! Single-instance lease so two keepers sharing a key don't race each other.
+//!
+//! The lease is a small JSON file (`LEASE_FILE`) on a volume every instance can see, holding
+//! the owner's id and an expiry time. The owner renews it every `LEASE_TTL_SECS / 3`; any other
+//! instance stays in standby and takes over only once the expiry has passed, or right away if
+//! the owner released it on shutdown. The file is read and written under an exclusive lock, on
+//! the blocking thread pool so a slow volume never stalls the runtime.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, watch};
+use tracing::{debug, error, info, warn};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LeaseRecord {
+    holder: String,
+    /// Unix seconds.
+    expires_at: u64,
+}
+
+enum Claim {
+    /// Renewed; `at` is when the expiry was computed, under the file lock.
+    Held {
+        at: Instant,
+    },
+    Taken {
+        holder: String,
+        expires_at: u64,
+    },
+}
+
+#[derive(Debug)]
+pub struct Lease {
+    path: PathBuf,
+    holder: String,
+    ttl: Duration,
+    released: AtomicBool,
+}
+
+impl Lease {
+    pub fn new(path: PathBuf, ttl: Duration) -> Self {
+        Self {
+            path,
+            holder: holder_id(),
+            ttl,
+            released: AtomicBool::new(false),
+        }
+    }
+
+    /// Take or renew the lease unless another holder's lease is still valid.
+    fn claim(&self) -> io::Result<Claim> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        file.lock()?;
+
+        let mut raw = String::new();
+        file.read_to_string(&mut raw)?;
+        let at = Instant::now();
+        let now = unix_now();
+        if let Ok(rec) = serde_json::from_str::<LeaseRecord>(&raw)
+            && rec.holder != self.holder
+            && rec.expires_at > now
+        {
+            return Ok(Claim::Taken {
+                holder: rec.holder,
+                expires_at: rec.expires_at,
+            });
+        }
+
+        let rec = LeaseRecord {
+            holder: self.holder.clone(),
+            expires_at: now + self.ttl.as_secs(),
+        };
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&serde_json::to_vec(&rec)?)?;
+        file.sync_data()?;
+        Ok(Claim::Held { at })
+    }
+
+    /// Give the lease up so a standby instance can take over without waiting for the expiry.
+    fn release(&self) -> io::Result<bool> {
+        self.released.store(true, Ordering::SeqCst);
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        file.lock()?;
+
+        let mut raw = String::new();
+        file.read_to_string(&mut raw)?;
+        if let Ok(rec) = serde_json::from_str::<LeaseRecord>(&raw)
+            && rec.holder == self.holder
+        {
+            file.set_len(0)?;
+            file.sync_data()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Keep claiming the lease in the background. `wake` is notified when it is acquired so a
+    /// standby loop doesn't sleep through the takeover.
+    pub fn spawn(self, wake: Arc<Notify>) -> LeaseWatch {
+        let lease = Arc::new(self);
+        let (tx, rx) = watch::channel(None);
+        let renew = lease.ttl / 3;
+        info!(
+            "keeper lease: path={} holder={} ttl_secs={}",
+            lease.path.display(),
+            lease.holder,
+            lease.ttl.as_secs()
+        );
+
+        let task_lease = lease.clone();
+        tokio::spawn(async move {
+            let lease = task_lease;
+            let mut held = false;
+            let mut standby_holder: Option<String> = None;
+
+            while !lease.released.load(Ordering::SeqCst) {
+                let claim_lease = lease.clone();
+                let res = tokio::task::spawn_blocking(move || claim_lease.claim())
+                    .await
+                    .unwrap_or_else(|e| Err(io::Error::other(e)));
+
+                match res {
+                    Ok(Claim::Held { at }) => {
+                        if !held {
+                            info!("keeper lease acquired: holder={}", lease.holder);
+                            wake.notify_one();
+                        }
+                        held = true;
+                        standby_holder = None;
+                        // Stop acting one renewal before the recorded expiry, so a missed
+                        // renewal never overlaps with a standby taking over. A slow or failed
+                        // claim doesn't move this; only a successful write does.
+                        tx.send_replace(Some(at + renew * 2));
+                    }
+                    Ok(Claim::Taken { holder, expires_at }) => {
+                        let expires_in = expires_at.saturating_sub(unix_now());
+                        if held {
+                            error!(
+                                "keeper lease taken over by another instance: holder={} expires_in_secs={}",
+                                holder, expires_in
+                            );
+                        } else if standby_holder.as_deref() != Some(&holder) {
+                            info!(
+                                "standby: keeper lease held by another instance: holder={} expires_in_secs={}",
+                                holder, expires_in
+                            );
+                        } else {
+                            debug!("standby: holder={} expires_in_secs={}", holder, expires_in);
+                        }
+                        held = false;
+                        standby_holder = Some(holder);
+                        tx.send_replace(None);
+                    }
+                    // Keep the previous deadline; it runs out on its own if this persists.
+                    Err(e) => warn!(
+                        "keeper lease renewal failed: path={} err={e}",
+                        lease.path.display()
+                    ),
+                }
+
+                tokio::time::sleep(renew).await;
+            }
+        });
+
+        LeaseWatch { lease, rx }
+    }
+}
+
+/// Read side of the background lease task.
+#[derive(Clone, Debug)]
+pub struct LeaseWatch {
+    lease: Arc<Lease>,
+    rx: watch::Receiver<Option<Instant>>,
+}
+
+impl LeaseWatch {
+    /// Whether the last successful renewal is still recent enough to act on.
+    pub fn held(&self) -> bool {
+        self.rx.borrow().is_some_and(|until| Instant::now() < until)
+    }
+
+    pub async fn release(&self) {
+        let lease = self.lease.clone();
+        let res = tokio::task::spawn_blocking(move || lease.release())
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e)));
+        match res {
+            Ok(true) => info!("keeper lease released: holder={}", self.lease.holder),
+            Ok(false) => {}
+            Err(e) => warn!(
+                "keeper lease release failed: path={} err={e}",
+                self.lease.path.display()
+            ),
+        }
+    }
+}
+
+/// Unique per process, readable in logs: `host/pid/random`.
+fn holder_id() -> String {
+    let host = std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_owned())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".into());
+    let nonce: u32 = rand::thread_rng().r#gen();
+    format!("{host}/{}/{nonce:08x}", std::process::id())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,191 @@
+//! Log output: human-readable text (default) or one JSON object per line (`LOG_FORMAT=json`).
+//!
+//! Key events carry tracing fields with stable names so log queries don't have to parse
+//! messages: `event` names what happened (`round_changed`, `tx_sent`, `tx_confirmed`,
+//! `tx_reverted`, `transfer_bond_sending`, `withdraw_fees_sending`, `locked_snapshot`, ...),
+//! alongside `round`, `action`, `tx_hash`, `amount_wei` and `status` where they apply. In JSON
+//! every field becomes a top-level key next to `timestamp`, `level`, `target` and `message`.
+//!
+//! Spans are only collected for the OTLP exporter (see `otlp.rs`) and never show up in log
+//! lines.
+
+use std::fmt;
+
+use serde_json::Value;
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{
+    EnvFilter, Layer,
+    filter::{FilterExt, filter_fn},
+    fmt::{
+        self as tfmt, FmtContext, FormatEvent, FormatFields,
+        format::Writer,
+        time::{FormatTime, SystemTime},
+    },
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+};
+
+use crate::{
+    AppError,
+    otlp::{self, OtlpConfig, OtlpExporter},
+    rpc::redact_url,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "text" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// `RUST_LOG` filters log lines as before (default `info`). Starts the OTLP exporter when
+/// configured. On a bad `LOG_FORMAT` or OTLP setting, logging still comes up (as text, without
+/// export) and the error is returned so the caller can fail on it.
+pub fn init_logging() -> Result<Option<OtlpExporter>, AppError> {
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(f) => f,
+        Err(_) => EnvFilter::new("info"),
+    };
+    let mut err = None;
+
+    let format = match std::env::var("LOG_FORMAT") {
+        Ok(raw) if !raw.trim().is_empty() => LogFormat::parse(&raw).unwrap_or_else(|| {
+            err = Some(AppError::BadEnv(
+                "LOG_FORMAT",
+                format!("{raw:?}, expected one of: text, json"),
+            ));
+            LogFormat::Text
+        }),
+        _ => LogFormat::Text,
+    };
+
+    let otlp_cfg = OtlpConfig::from_env().unwrap_or_else(|e| {
+        err.get_or_insert(e);
+        None
+    });
+    let otlp_target = otlp_cfg
+        .as_ref()
+        .map(|c| (redact_url(&c.traces_url), c.service_name.clone()));
+    let (otlp_layer, exporter) = match otlp_cfg.map(otlp::spawn).transpose() {
+        Ok(Some((layer, exporter))) => (Some(layer), Some(exporter)),
+        Ok(None) => (None, None),
+        Err(e) => {
+            err.get_or_insert(e);
+            (None, None)
+        }
+    };
+
+    let lines = filter.and(filter_fn(|meta| meta.is_event()));
+    let fmt_layer = match format {
+        LogFormat::Text => tfmt::layer().with_filter(lines).boxed(),
+        LogFormat::Json => tfmt::layer()
+            .event_format(JsonFormat)
+            .with_filter(lines)
+            .boxed(),
+    };
+    let otlp_layer = otlp_layer.map(|l| {
+        l.with_filter(filter_fn(|meta| {
+            meta.is_span() && meta.target().starts_with("funds_transfer")
+        }))
+    });
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(otlp_layer)
+        .init();
+
+    if let Some((url, service)) = otlp_target
+        && exporter.is_some()
+    {
+        tracing::info!("otlp trace export: url={} service={}", url, service);
+    }
+
+    match err {
+        Some(e) => Err(e),
+        None => Ok(exporter),
+    }
+}
+
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+
+        // Kept in insertion order so every line starts with the same keys.
+        let meta = event.metadata();
+        let mut fields: Vec<(String, Value)> = vec![
+            ("timestamp".into(), timestamp.into()),
+            ("level".into(), meta.level().as_str().into()),
+            ("target".into(), meta.target().into()),
+        ];
+        event.record(&mut JsonVisitor(&mut fields));
+
+        writer.write_char('{')?;
+        for (i, (key, value)) in fields.iter().enumerate() {
+            if i > 0 {
+                writer.write_char(',')?;
+            }
+            let key = serde_json::to_string(key).map_err(|_| fmt::Error)?;
+            let value = serde_json::to_string(value).map_err(|_| fmt::Error)?;
+            write!(writer, "{key}:{value}")?;
+        }
+        writer.write_str("}\n")
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Vec<(String, Value)>);
+
+impl JsonVisitor<'_> {
+    fn insert(&mut self, field: &Field, value: Value) {
+        self.0.push((field.name().into(), value));
+    }
+}
+
+impl Visit for JsonVisitor<'_> {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{value:?}").into());
+    }
+}
@@ -0,0 +1,1965 @@
+use std::{
+    env, fmt,
+    io::Write,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+    time::Duration,
+};
+
+use ethers::{
+    contract::{Multicall, abigen},
+    middleware::SignerMiddleware,
+    providers::{Middleware, Provider},
+    signers::{LocalWallet, Signer},
+    types::{Address, U64, U256},
+};
+use tokio::sync::Notify;
+use tracing::{Instrument, debug, error, field, info, info_span, warn};
+use url::Url;
+
+mod backup;
+mod balance;
+mod events;
+mod health;
+mod hints;
+mod http;
+mod lease;
+mod logging;
+mod metrics;
+mod node;
+#[cfg(test)]
+mod node_stub;
+mod notify;
+mod otlp;
+mod payout;
+mod quorum;
+mod reads;
+mod retain;
+mod retry;
+mod reward_watch;
+mod rpc;
+mod scheduler;
+mod shutdown;
+mod tx;
+
+use backup::{BackupKeeper, BackupPolicy};
+use balance::{BalanceGuard, BalanceGuardConfig, Funds};
+use events::EventSource;
+use health::Health;
+use hints::{Hints, Pool};
+use http::HttpState;
+use lease::Lease;
+use metrics::Metrics;
+use node::{NodeAction, NodeClient};
+use notify::{Notification, Notifier, NotifyConfig, NotifyEvent};
+use payout::Split;
+use quorum::{LockedValues, QuorumGate};
+use reads::{ChainReader, ChainReads, ReadPlan};
+use retain::RetainPolicy;
+use retry::{ErrorKind, Retries, RetryPolicy};
+use reward_watch::{RewardWatch, RewardWatchConfig};
+use rpc::{EndpointSpec, FailoverClient, RpcTimeouts};
+use scheduler::{SchedulerConfig, SchedulerMode};
+use shutdown::Shutdown;
+use tx::TxSender;
+
+/// go-livepeer's default `-cliAddr`.
+const DEFAULT_NODE_CLI_URL: &str = "http://127.0.0.1:7935";
+/// Added to the longest tx wait when judging whether a loop iteration is overdue.
+const HEALTH_WORK_SLACK_SECS: u64 = 60;
+/// Payout key of the fee withdrawal legs.
+const WITHDRAW_FEES: &str = "withdrawFees";
+/// Payout key of the bonded LPT transfer legs.
+const TRANSFER_BOND: &str = "transferBond";
+
+abigen!(
+    BondingManager,
+    "src/abi/BondingManager.json",
+    event_derives(serde::Deserialize, serde::Serialize)
+);
+
+abigen!(
+    RoundsManager,
+    "src/abi/RoundsManager.json",
+    event_derives(serde::Deserialize, serde::Serialize)
+);
+#[derive(Clone, Debug)]
+struct Config {
+    http_rpc_endpoints: Vec<EndpointSpec>,
+    ws_rpc: Option<EndpointSpec>,
+    // eth_sendRawTransaction only (optional); receipts stay on the read endpoints
+    broadcast_rpc_endpoints: Option<Vec<EndpointSpec>>,
+    broadcast_fan_out: bool,
+    rpc_timeouts: RpcTimeouts,
+    chain_id: u64,
+
+    rounds_manager_addr: Address,
+    bonding_manager_addr: Address,
+
+    // Signing key; not needed with the node executor
+    json_key_file: Option<String>,
+    passphrase_file: Option<String>,
+    orchestrator_addr: Option<Address>,
+
+    // Quorum confirmation before transferBond/withdrawFees (optional)
+    quorum_rpc_endpoints: Vec<EndpointSpec>,
+    quorum_min: Option<usize>,
+
+    // Batched reads via Multicall3 (falls back to individual calls)
+    enable_multicall: bool,
+    multicall_addr: Option<Address>,
+
+    // Loop timing
+    loop_sleep_secs: u64,
+    scheduler_mode: SchedulerMode,
+    l1_block_time_secs: u64,
+    wake_margin_secs: u64,
+    max_sleep_secs: u64,
+    // Tx receipt wait timeout
+    receipt_timeout_secs: u64,
+    // Per-action retry backoff
+    retry_base_secs: u64,
+    retry_max_secs: u64,
+    retry_max_attempts: u32,
+    // Executor backend: sign here (None) or drive a go-livepeer node's CLI API
+    node_cli_url: Option<Url>,
+    node_cli_timeout_secs: u64,
+    // Shutdown and persisted state
+    state_dir: Option<PathBuf>,
+    shutdown_grace_secs: u64,
+    // Single-instance lease (optional)
+    lease_file: Option<PathBuf>,
+    lease_ttl_secs: u64,
+    // Backup keeper mode (optional): act only when the primary missed an action
+    backup: Option<BackupPolicy>,
+    // HTTP listener for /metrics, /healthz and /readyz (optional)
+    metrics_addr: Option<SocketAddr>,
+    health_max_missed_loops: u32,
+    // Signer balance runway (optional)
+    balance_guard: Option<BalanceGuardConfig>,
+
+    // Reward call (optional)
+    enable_reward: bool,
+    // Missed-reward detection
+    missed_reward_check: bool,
+    reward_watch: RewardWatchConfig,
+
+    // Bond transfer (optional)
+    enable_transfer_bond: bool,
+    lpt_split: Option<Split>,
+    lpt_retain: Option<RetainPolicy>,
+
+    // Fee withdrawal (optional)
+    enable_withdraw_fees: bool,
+    // ETH_FEE_RECEIVERS, or all of it to ETH_FEE_RECEIVER_ADDR
+    eth_fee_split: Option<Split>,
+    eth_fee_withdraw_threshold_wei: Option<U256>,
+    // Refill the signer from withdrawn fees up to this balance (optional)
+    gas_topup_target_wei: Option<U256>,
+}
+
+#[derive(Debug)]
+enum AppError {
+    MissingEnv(&'static str),
+    BadEnv(&'static str, String),
+    Wallet(String),
+    /// RPC, contract or tx failure, classified for retries.
+    Chain(ErrorKind, String),
+    Quorum(String),
+    /// Cut short by a shutdown request or a lost keeper lease; not a failure of the action.
+    Interrupted(String),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::MissingEnv(k) => write!(f, "missing env var: {k}"),
+            AppError::BadEnv(k, v) => write!(f, "invalid env var {k}: {v}"),
+            AppError::Wallet(e) => write!(f, "wallet error: {e}"),
+            AppError::Chain(kind, e) => write!(f, "{kind} error: {e}"),
+            AppError::Quorum(e) => write!(f, "quorum not reached: {e}"),
+            AppError::Interrupted(e) => write!(f, "interrupted: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RoundState {
+    round: U256,
+    initialized: bool,
+    locked: bool,
+}
+
+/// Withdrawn fees go to the signer first, up to `target`, when it is short of gas.
+#[derive(Clone, Copy, Debug)]
+struct GasTopUp {
+    signer: Address,
+    target: U256,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct LockedSnapshot {
+    round: U256,
+    pending_stake: U256,
+    pending_fees: U256,
+    stake_present: bool,
+    fees_present: bool,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "healthcheck") {
+        return ExitCode::from(health::healthcheck(&args[1..]));
+    }
+
+    let otlp = match logging::init_logging() {
+        Ok(otlp) => otlp,
+        Err(e) => {
+            error!("fatal: {e}");
+            return ExitCode::from(shutdown::EXIT_CONFIG);
+        }
+    };
+
+    let notifier = match NotifyConfig::from_env().and_then(|c| c.map(Notifier::spawn).transpose()) {
+        Ok(n) => n.unwrap_or_default(),
+        Err(e) => {
+            error!("fatal: {e}");
+            return ExitCode::from(shutdown::EXIT_CONFIG);
+        }
+    };
+
+    let code = match run(notifier.clone()).await {
+        Ok(code) => code,
+        Err(e) => {
+            error!("fatal: {e}");
+            notifier.notify(
+                Notification::new(NotifyEvent::FatalError, "funds_transfer stopped")
+                    .field("kind", e.kind())
+                    .field("error", &e),
+            );
+            if e.kind() == ErrorKind::Config {
+                shutdown::EXIT_CONFIG
+            } else {
+                shutdown::EXIT_FAILURE
+            }
+        }
+    };
+    notifier.flush(notify::FLUSH_TIMEOUT).await;
+    if let Some(otlp) = otlp {
+        otlp.flush(otlp::FLUSH_TIMEOUT).await;
+    }
+    let _ = std::io::stdout().flush();
+    ExitCode::from(code)
+}
+
+/// Runs until shutdown is requested; returns the process exit code.
+async fn run(notifier: Notifier) -> Result<u8, AppError> {
+    let cfg = load_config()?;
+    validate_config(&cfg)?;
+
+    // Woken by the WebSocket listener (if configured) and by shutdown signals so we react
+    // without waiting out a sleep.
+    let wake = Arc::new(Notify::new());
+    let shutdown = Shutdown::listen(Duration::from_secs(cfg.shutdown_grace_secs), wake.clone());
+
+    info!(
+        "starting funds_transfer: chain_id={} rounds_manager={:?} bonding_manager={:?} sleep_secs={} scheduler={:?} ws_events={} flags(reward={}, transfer_bond={}, withdraw_fees={})",
+        cfg.chain_id,
+        cfg.rounds_manager_addr,
+        cfg.bonding_manager_addr,
+        cfg.loop_sleep_secs,
+        cfg.scheduler_mode,
+        cfg.ws_rpc.is_some(),
+        cfg.enable_reward,
+        cfg.enable_transfer_bond,
+        cfg.enable_withdraw_fees
+    );
+
+    // Reads and broadcasts both go through the failover transport; broadcasts use their own
+    // endpoints when BROADCAST_RPC_URLS is set.
+    let mut rpc = FailoverClient::new(cfg.http_rpc_endpoints.clone())
+        .map_err(|e| AppError::Chain(ErrorKind::Config, e))?
+        .with_timeouts(cfg.rpc_timeouts);
+    if let Some(specs) = cfg.broadcast_rpc_endpoints.clone() {
+        rpc = rpc
+            .with_broadcast(specs, cfg.broadcast_fan_out)
+            .map_err(|e| AppError::Chain(ErrorKind::Config, e))?;
+        info!(
+            "tx broadcast: dedicated endpoints fan_out={}",
+            cfg.broadcast_fan_out
+        );
+    }
+    for (label, priority) in rpc.describe() {
+        info!("rpc endpoint: endpoint={} priority={}", label, priority);
+    }
+    info!(
+        "rpc timeouts: read_secs={:.1} estimate_secs={:.1} broadcast_secs={:.1}",
+        cfg.rpc_timeouts.read.as_secs_f32(),
+        cfg.rpc_timeouts.estimate.as_secs_f32(),
+        cfg.rpc_timeouts.broadcast.as_secs_f32()
+    );
+    let rpc = Arc::new(rpc);
+    let provider = Provider::new(rpc.clone());
+    // internal polling interval for provider housekeeping
+    let provider = provider.interval(Duration::from_millis(250));
+
+    // Served before the wallet is loaded so /readyz can report startup progress.
+    let metrics = Arc::new(Metrics::default());
+    let work_allowance = Duration::from_secs(
+        HEALTH_WORK_SLACK_SECS
+            + match cfg.node_cli_url {
+                Some(_) => cfg.node_cli_timeout_secs,
+                None => cfg.receipt_timeout_secs,
+            },
+    );
+    let health = Arc::new(Health::new(
+        cfg.health_max_missed_loops,
+        Duration::from_secs(cfg.loop_sleep_secs),
+        work_allowance,
+    ));
+    if let Some(addr) = cfg.metrics_addr {
+        http::serve(
+            addr,
+            HttpState {
+                metrics: metrics.clone(),
+                health: health.clone(),
+                rpc: rpc.clone(),
+            },
+        )
+        .await?;
+    }
+
+    let node = match &cfg.node_cli_url {
+        Some(url) => {
+            let node =
+                NodeClient::new(url.clone(), Duration::from_secs(cfg.node_cli_timeout_secs))?;
+            let status = node.status().await?;
+            info!(
+                "node executor: node={} version={}",
+                node.describe(),
+                status.get("Version").unwrap_or(&serde_json::Value::Null)
+            );
+            Some(node)
+        }
+        None => None,
+    };
+
+    let (wallet, signer_addr) = match &node {
+        // The node signs; this throwaway key only completes the middleware stack and never
+        // signs anything, since every action goes through the node.
+        Some(node) => (
+            LocalWallet::new(&mut rand::thread_rng()).with_chain_id(cfg.chain_id),
+            node.eth_addr().await?,
+        ),
+        None => {
+            let wallet = load_wallet(&cfg)?;
+            let addr = wallet.address();
+            (wallet, addr)
+        }
+    };
+    health.signer_loaded();
+    let orchestrator_addr = cfg.orchestrator_addr.unwrap_or(signer_addr);
+
+    // The node's /withdrawFees takes no receiver and pays the node's own account.
+    if node.is_some()
+        && let Some(split) = &cfg.eth_fee_split
+        && split.receivers().any(|r| r != signer_addr)
+    {
+        return Err(AppError::BadEnv(
+            "ETH_FEE_RECEIVER_ADDR",
+            format!("must be the node's address {signer_addr:?} with EXECUTOR=node"),
+        ));
+    }
+
+    if orchestrator_addr != signer_addr {
+        warn!(
+            "ORCHESTRATOR_ADDR differs from signer address; using orchestrator={:?} signer={:?}",
+            orchestrator_addr, signer_addr
+        );
+    } else {
+        info!("orchestrator/signer address: {:?}", orchestrator_addr);
+    }
+
+    let client = Arc::new(SignerMiddleware::new(provider, wallet));
+    let rounds = RoundsManager::new(cfg.rounds_manager_addr, client.clone());
+    let bonding = BondingManager::new(cfg.bonding_manager_addr, client.clone());
+
+    let multicall = if cfg.enable_multicall {
+        match Multicall::new_with_chain_id(client.clone(), cfg.multicall_addr, Some(cfg.chain_id)) {
+            Ok(mc) => Some(mc),
+            Err(e) => {
+                warn!(
+                    "multicall unavailable ({e}); set MULTICALL_ADDR to enable batched reads, using individual calls"
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+    info!("batched reads via multicall3: {}", multicall.is_some());
+    if let Some(b) = cfg.backup {
+        info!(
+            "backup keeper mode: reward_delay_blocks={} locked_delay_blocks={}",
+            b.reward_delay_blocks, b.locked_delay_blocks
+        );
+    }
+
+    let mut reader = ChainReader::new(
+        client.clone(),
+        rounds.clone(),
+        bonding.clone(),
+        multicall,
+        orchestrator_addr,
+        ReadPlan {
+            schedule: cfg.scheduler_mode == SchedulerMode::Block || cfg.backup.is_some(),
+            transcoder: cfg.enable_reward || cfg.missed_reward_check,
+            pending_stake: cfg.enable_transfer_bond,
+            pending_fees: cfg.enable_withdraw_fees,
+            total_stake: cfg.lpt_retain.is_some_and(|p| p.needs_total_stake()),
+            balance_of: (cfg.metrics_addr.is_some()
+                || cfg.balance_guard.is_some()
+                || cfg.gas_topup_target_wei.is_some())
+            .then_some(signer_addr),
+        },
+    );
+
+    let sched_cfg = SchedulerConfig {
+        mode: cfg.scheduler_mode,
+        loop_sleep_secs: cfg.loop_sleep_secs,
+        l1_block_time_secs: cfg.l1_block_time_secs,
+        wake_margin_secs: cfg.wake_margin_secs,
+        max_sleep_secs: cfg.max_sleep_secs,
+    };
+
+    let quorum = match cfg.quorum_min {
+        Some(min_agree) => Some(QuorumGate::new(
+            &cfg.quorum_rpc_endpoints,
+            cfg.rpc_timeouts,
+            min_agree,
+            cfg.rounds_manager_addr,
+            cfg.bonding_manager_addr,
+            notifier.clone(),
+        )?),
+        None => None,
+    };
+
+    let retries = Retries::new(
+        RetryPolicy {
+            base_secs: cfg.retry_base_secs,
+            max_secs: cfg.retry_max_secs,
+            max_attempts: cfg.retry_max_attempts,
+        },
+        &notifier,
+    );
+    // With LEASE_FILE set, only the instance holding the lease acts; the others stand by.
+    let lease = cfg
+        .lease_file
+        .clone()
+        .map(|path| Lease::new(path, Duration::from_secs(cfg.lease_ttl_secs)).spawn(wake.clone()));
+    let mut sender = TxSender::new(
+        retries,
+        cfg.receipt_timeout_secs,
+        shutdown,
+        cfg.state_dir.as_deref(),
+    )
+    .with_lease(lease.clone())
+    .with_node(node)
+    .with_metrics(metrics.clone())
+    .with_notifier(notifier.clone());
+
+    if let Some(ws) = cfg.ws_rpc.clone() {
+        events::spawn_listener(
+            EventSource {
+                ws,
+                rounds_manager_addr: cfg.rounds_manager_addr,
+                bonding_manager_addr: cfg.bonding_manager_addr,
+                orchestrator: orchestrator_addr,
+                own_txs: sender.own_txs(),
+            },
+            wake.clone(),
+        );
+    }
+
+    if let Some(split) = &cfg.lpt_split {
+        info!("LPT receivers: {}", split.describe());
+    }
+    if let Some(policy) = &cfg.lpt_retain {
+        info!("LPT retain policy: {policy}");
+    }
+    if let Some(split) = &cfg.eth_fee_split {
+        info!("fee receivers: {}", split.describe());
+    }
+    let topup = cfg.gas_topup_target_wei.map(|target| {
+        info!("gas top-up from fees: signer={signer_addr:?} target_wei={target}");
+        GasTopUp {
+            signer: signer_addr,
+            target,
+        }
+    });
+
+    let mut balance_guard = cfg.balance_guard.map(|guard_cfg| {
+        let actions = [
+            (cfg.enable_reward, "reward"),
+            (cfg.enable_transfer_bond, "transferBond"),
+            (cfg.enable_withdraw_fees, "withdrawFees"),
+        ];
+        BalanceGuard::new(
+            client.clone(),
+            guard_cfg,
+            actions
+                .iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, a)| *a)
+                .collect(),
+            metrics.clone(),
+            notifier.clone(),
+        )
+    });
+
+    let mut backup = cfg.backup.map(BackupKeeper::new);
+    let mut reward_watch = cfg.missed_reward_check.then(|| {
+        RewardWatch::new(
+            client.clone(),
+            cfg.rounds_manager_addr,
+            cfg.bonding_manager_addr,
+            orchestrator_addr,
+            cfg.reward_watch,
+            cfg.state_dir.as_deref(),
+            metrics.clone(),
+            notifier.clone(),
+        )
+    });
+
+    let mut recovered = false;
+    let mut chain_id_verified = false;
+    let mut read_failures: u32 = 0;
+
+    let mut last_state: Option<RoundState> = None;
+    let mut last_locked_snapshot: Option<LockedSnapshot> = None;
+    // Rounds where reward() was seen done, and where we sent it ourselves; go-livepeer may
+    // call it with the same key.
+    let mut reward_seen_round: Option<U256> = None;
+    let mut reward_sent_round: Option<U256> = None;
+
+    loop {
+        if sender.stopping() {
+            break;
+        }
+
+        if !chain_id_verified {
+            match verify_chain_id(client.as_ref(), cfg.chain_id).await {
+                Ok(()) => {
+                    chain_id_verified = true;
+                    health.chain_id_verified();
+                }
+                Err(e) if e.kind() == ErrorKind::Config => return Err(e),
+                Err(e) => {
+                    warn!("chain id check failed: {e}; retrying");
+                    // Counted with the chain reads so an RPC down at startup is reported too.
+                    read_failures += 1;
+                    if notifier.failure_alert_due(read_failures, false) {
+                        notifier.notify(
+                            Notification::new(
+                                NotifyEvent::RepeatedFailure,
+                                format!("chain id check failed {read_failures} times in a row"),
+                            )
+                            .field("kind", e.kind())
+                            .field("error", &e),
+                        );
+                    }
+                    health.rpc_reachable(false);
+                    health.beat(Duration::from_secs(cfg.loop_sleep_secs));
+                    events::sleep_or_wake(Duration::from_secs(cfg.loop_sleep_secs), &wake).await;
+                    continue;
+                }
+            }
+        }
+
+        if let Some(lease) = &lease
+            && !lease.held()
+        {
+            // Pick up whatever the holder left in flight once we take over.
+            recovered = false;
+            health.beat(Duration::from_secs(cfg.loop_sleep_secs));
+            events::sleep_or_wake(Duration::from_secs(cfg.loop_sleep_secs), &wake).await;
+            continue;
+        }
+        // Parent of this iteration's rpc and tx spans when OTLP export is on.
+        let loop_span = info_span!(
+            "loop",
+            round = field::Empty,
+            block = field::Empty,
+            settled = field::Empty,
+            error = field::Empty
+        );
+
+        if !recovered {
+            sender
+                .recover(client.as_ref())
+                .instrument(loop_span.clone())
+                .await;
+            recovered = true;
+        }
+
+        let reads = match reader.fetch().instrument(loop_span.clone()).await {
+            Ok(r) => {
+                read_failures = 0;
+                metrics.observe_reads(&r);
+                health.rpc_reachable(true);
+                r
+            }
+            Err(e) => {
+                read_failures += 1;
+                metrics.read_failed();
+                health.rpc_reachable(false);
+                let delay = sender.retries.policy.delay(e.kind(), read_failures);
+                warn!(
+                    "failed to fetch round state: attempt={} kind={} retry_in_secs={} err={}",
+                    read_failures,
+                    e.kind(),
+                    delay.as_secs(),
+                    e
+                );
+                if notifier.failure_alert_due(read_failures, false) {
+                    notifier.notify(
+                        Notification::new(
+                            NotifyEvent::RepeatedFailure,
+                            format!("chain reads failed {read_failures} times in a row"),
+                        )
+                        .field("kind", e.kind())
+                        .field("error", &e),
+                    );
+                }
+                loop_span.record("error", field::display(&e));
+                drop(loop_span);
+                health.beat(delay);
+                events::sleep_or_wake(delay, &wake).await;
+                continue;
+            }
+        };
+        let state = reads.state;
+        if let Some(b) = &mut backup {
+            b.observe(&reads);
+        }
+        loop_span.record("round", field::display(state.round));
+        loop_span.record("block", reads.block.as_u64());
+
+        let state_changed = last_state.map(|ls| ls != state).unwrap_or(true);
+        if state_changed {
+            info!(
+                event = "round_changed",
+                round = %state.round,
+                initialized = state.initialized,
+                locked = state.locked,
+                block = reads.block.as_u64(),
+                "round state changed"
+            );
+            rpc.log_health();
+        } else {
+            debug!(
+                "round unchanged: round={} initialized={} locked={} block={}",
+                state.round, state.initialized, state.locked, reads.block
+            );
+        }
+
+        // Anything sent or failed this loop keeps the fixed interval for the next one.
+        let mut settled = true;
+
+        let funds = match &mut balance_guard {
+            Some(guard) => guard.check(&reads).instrument(loop_span.clone()).await,
+            None => Funds::Ok,
+        };
+
+        // 1) When initialized: reward() once per round
+        if cfg.enable_reward
+            && state.initialized
+            && let Some(last_reward_round) = reads.last_reward_round
+        {
+            if last_reward_round >= state.round && reward_seen_round != Some(state.round) {
+                reward_seen_round = Some(state.round);
+                if reward_sent_round != Some(state.round) {
+                    info!(
+                        event = "reward_by_other_sender",
+                        round = %state.round,
+                        last_reward_round = %last_reward_round,
+                        "reward already called by another sender (not this process)"
+                    );
+                }
+            }
+
+            if !sender.retries.reward.ready(state.round) {
+                debug!("reward deferred by retry backoff: round={}", state.round);
+            } else if last_reward_round < state.round
+                && backup.as_mut().is_some_and(|b| b.defer_reward(&reads))
+            {
+                // Keep polling until the takeover point.
+                settled = false;
+            } else {
+                match maybe_reward_once_per_round(&bonding, last_reward_round, state.round, &sender)
+                    .instrument(loop_span.clone())
+                    .await
+                {
+                    Ok(sent) => {
+                        if sent {
+                            sender.retries.reward.succeeded();
+                            reward_sent_round = Some(state.round);
+                        }
+                        settled &= !sent;
+                    }
+                    Err(e) => {
+                        sender.retries.reward.failed(&sender.retries.policy, &e);
+                        settled = false;
+                    }
+                }
+            }
+        }
+
+        // 2) When locked: transferBond + withdrawFees
+        if (cfg.enable_transfer_bond || cfg.enable_withdraw_fees)
+            && state.locked
+            && !sender.stopping()
+        {
+            if funds == Funds::Critical && topup.is_none() {
+                // Whatever is left is kept for reward().
+                debug!("locked-round actions paused: signer balance critical");
+            } else {
+                match handle_locked_round_actions(
+                    &bonding,
+                    &reader,
+                    orchestrator_addr,
+                    &reads,
+                    funds,
+                    topup,
+                    quorum.as_ref(),
+                    &cfg,
+                    backup.as_mut(),
+                    &mut sender,
+                    &mut last_locked_snapshot,
+                )
+                .instrument(loop_span.clone())
+                .await
+                {
+                    Ok(sent) => settled &= !sent,
+                    Err(e) => {
+                        loop_span.record("error", field::display(&e));
+                        warn!(
+                            "locked-round actions failed: {e}; will retry next loop if still needed"
+                        );
+                        settled = false;
+                    }
+                }
+            }
+        }
+
+        // 3) Rounds that closed since the last loop: was reward() called in each?
+        if let Some(watch) = &mut reward_watch
+            && let Err(e) = watch.check(&reads).instrument(loop_span.clone()).await
+        {
+            warn!("missed-reward check failed: {e}; will retry later");
+        }
+
+        last_state = Some(state);
+        metrics.loop_completed();
+        let mut sleep = scheduler::next_sleep(&sched_cfg, &state, reads.schedule.as_ref(), settled);
+        // Wake up for the earliest pending retry rather than sleeping through it.
+        if let Some(wait) = sender.retries.next_wait() {
+            sleep = sleep.min(wait.max(Duration::from_secs(1)));
+        }
+        loop_span.record("settled", settled);
+        drop(loop_span);
+        health.beat(sleep);
+        events::sleep_or_wake(sleep, &wake).await;
+    }
+
+    if let Some(lease) = &lease {
+        lease.release().await;
+    }
+
+    let inflight = sender.inflight_count();
+    if inflight > 0 {
+        warn!(
+            "stopped with unconfirmed txs: count={} state_dir={:?}",
+            inflight, cfg.state_dir
+        );
+        return Ok(shutdown::EXIT_INFLIGHT);
+    }
+    info!("stopped cleanly");
+    Ok(shutdown::EXIT_OK)
+}
+
+/// Fails with a config error if the RPC serves a different chain than `CHAIN_ID`.
+async fn verify_chain_id<M: Middleware + 'static>(
+    client: &M,
+    expected: u64,
+) -> Result<(), AppError> {
+    let actual = client
+        .get_chainid()
+        .await
+        .map_err(|e| AppError::middleware("eth_chainId", &e))?;
+    if actual != U256::from(expected) {
+        return Err(AppError::BadEnv(
+            "CHAIN_ID",
+            format!("{expected} does not match the RPC's chain id {actual}"),
+        ));
+    }
+    info!(
+        event = "chain_id_verified",
+        chain_id = actual.as_u64(),
+        "chain id verified"
+    );
+    Ok(())
+}
+
+/// Calls bonding.reward() ONLY if lastRewardRound < current_round.
+/// Failures are returned to the caller, which decides when to retry.
+/// Returns whether a reward tx was sent.
+async fn maybe_reward_once_per_round<M: Middleware + 'static>(
+    bonding: &BondingManager<M>,
+    last_reward_round: U256,
+    current_round: U256,
+    sender: &TxSender,
+) -> Result<bool, AppError> {
+    if last_reward_round >= current_round {
+        debug!(
+            "reward not needed: lastRewardRound={} currentRound={}",
+            last_reward_round, current_round
+        );
+        return Ok(false);
+    }
+
+    info!(
+        event = "reward_needed",
+        round = %current_round,
+        last_reward_round = %last_reward_round,
+        "reward needed: lastRewardRound < currentRound (sending reward tx)"
+    );
+
+    if sender.uses_node() {
+        sender
+            .send_via_node(NodeAction::Reward, current_round)
+            .await?;
+    } else {
+        sender
+            .send_and_confirm(
+                bonding.client_ref(),
+                "reward",
+                bonding.reward(),
+                current_round,
+            )
+            .await?;
+    }
+    Ok(true)
+}
+
+/// Returns whether a transferBond or withdrawFees tx was attempted, or in backup mode is still
+/// left to the primary.
+/// With a quorum gate, the values are confirmed before the first tx; if the quorum is not
+/// reached nothing is sent. Quorum and tx failures are recorded in `sender.retries`; withdrawFees is not started once shutdown was requested.
+/// While `funds` is critical, only the gas top-up part of withdrawFees is sent.
+#[allow(clippy::too_many_arguments)]
+async fn handle_locked_round_actions<M: Middleware + 'static>(
+    bonding: &BondingManager<M>,
+    reader: &ChainReader<M>,
+    orchestrator: Address,
+    reads: &ChainReads,
+    funds: Funds,
+    topup: Option<GasTopUp>,
+    quorum: Option<&QuorumGate>,
+    cfg: &Config,
+    mut backup: Option<&mut BackupKeeper>,
+    sender: &mut TxSender,
+    last_locked_snapshot: &mut Option<LockedSnapshot>,
+) -> Result<bool, AppError> {
+    if !cfg.enable_transfer_bond && !cfg.enable_withdraw_fees {
+        debug!("locked-round actions skipped: both transferBond and withdrawFees are disabled");
+        return Ok(false);
+    }
+
+    let current_round = reads.state.round;
+    let expected = LockedValues {
+        round: current_round,
+        locked: reads.state.locked,
+        pending_stake: reads.pending_stake,
+        pending_fees: reads.pending_fees,
+        total_stake: reads.total_stake,
+    };
+    let mut sent = false;
+    let mut pending_stake_for_snapshot: Option<U256> = None;
+    let mut pending_fees_for_snapshot: Option<U256> = None;
+
+    // ----------------------------
+    // transferBond (optional)
+    // ----------------------------
+    if cfg.enable_transfer_bond && funds == Funds::Critical {
+        debug!("transferBond paused: signer balance critical");
+    } else if cfg.enable_transfer_bond {
+        let split = cfg.lpt_split.as_ref().ok_or_else(|| {
+            AppError::BadEnv(
+                "LPT_RECEIVER_ADDR",
+                "required when ENABLE_TRANSFER_BOND=true".into(),
+            )
+        })?;
+        let policy = cfg.lpt_retain.ok_or_else(|| {
+            AppError::BadEnv(
+                "LPT_RETAIN_POLICY",
+                "required when ENABLE_TRANSFER_BOND=true".into(),
+            )
+        })?;
+
+        let pending_stake = reads.pending_stake.ok_or_else(|| {
+            AppError::Chain(ErrorKind::Config, "pendingStake() was not read".into())
+        })?;
+        let total_stake = match reads.total_stake {
+            Some(t) => t,
+            None if policy.needs_total_stake() => {
+                return Err(AppError::Chain(
+                    ErrorKind::Config,
+                    "transcoderTotalStake() was not read".into(),
+                ));
+            }
+            None => U256::zero(),
+        };
+        let retain = policy.retain(pending_stake, total_stake);
+
+        pending_stake_for_snapshot = Some(pending_stake);
+
+        // Keep check logs at DEBUG to avoid redundant INFO spam.
+        debug!(
+            "transferBond check: round={} pendingStakeWei={} retainWei={}",
+            current_round, pending_stake, retain
+        );
+
+        let transferable = match pending_stake.checked_sub(retain) {
+            Some(v) if !v.is_zero() => v,
+            _ => {
+                debug!(
+                    "transferBond skipped: pendingStakeWei={} <= retainWei={} round={}",
+                    pending_stake, retain, current_round
+                );
+                U256::zero()
+            }
+        };
+
+        // Legs left from an earlier transfer are finished even if less is transferable now. A
+        // new one is only planned from the round's first read: each leg lowers pendingStake,
+        // and a share of what is left would find more to move on every loop.
+        let outstanding = sender.payouts.outstanding(TRANSFER_BOND).is_some();
+        let planned = sender.payouts.planned_in(TRANSFER_BOND, current_round);
+        if planned && !outstanding && !transferable.is_zero() {
+            debug!(
+                "transferBond skipped: payout already planned this round: round={} transferableWei={}",
+                current_round, transferable
+            );
+        }
+        let due = outstanding || (!transferable.is_zero() && !planned);
+
+        if due && !sender.retries.transfer_bond.ready(current_round) {
+            debug!(
+                "transferBond deferred by retry backoff: round={}",
+                current_round
+            );
+        } else if due
+            && backup
+                .as_deref_mut()
+                .is_some_and(|b| b.defer_locked("transferBond", reads))
+        {
+            sent = true;
+        } else if due {
+            let res = match confirm_quorum(quorum, orchestrator, reads, &expected).await {
+                Ok(()) => {
+                    transfer_bond(
+                        bonding,
+                        reader,
+                        reads.block,
+                        orchestrator,
+                        current_round,
+                        transferable,
+                        split,
+                        sender,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(false) => {}
+                Ok(true) => {
+                    sent = true;
+                    sender.retries.transfer_bond.succeeded();
+                }
+                Err(e) => {
+                    sent = true;
+                    sender
+                        .retries
+                        .transfer_bond
+                        .failed(&sender.retries.policy, &e);
+                }
+            }
+        }
+    }
+
+    // ----------------------------
+    // withdrawFees (optional)
+    // ----------------------------
+    if cfg.enable_withdraw_fees && !sender.stopping() {
+        let split = cfg.eth_fee_split.as_ref().ok_or_else(|| {
+            AppError::BadEnv(
+                "ETH_FEE_RECEIVER_ADDR",
+                "required when ENABLE_WITHDRAW_FEES=true".into(),
+            )
+        })?;
+        let threshold = cfg.eth_fee_withdraw_threshold_wei.ok_or_else(|| {
+            AppError::BadEnv(
+                "ETH_FEE_WITHDRAW_THRESHOLD_WEI",
+                "required when ENABLE_WITHDRAW_FEES=true".into(),
+            )
+        })?;
+
+        let pending_fees = reads.pending_fees.ok_or_else(|| {
+            AppError::Chain(ErrorKind::Config, "pendingFees() was not read".into())
+        })?;
+
+        pending_fees_for_snapshot = Some(pending_fees);
+
+        debug!(
+            "withdrawFees check: round={} pendingFeesWei={} thresholdWei={}",
+            current_round, pending_fees, threshold
+        );
+
+        // Legs left from an earlier payout are finished even if what is left is below the
+        // threshold.
+        let outstanding = sender.payouts.outstanding(WITHDRAW_FEES).is_some();
+        let due = outstanding || (pending_fees >= threshold && !pending_fees.is_zero());
+
+        if due && !sender.retries.withdraw_fees.ready(current_round) {
+            debug!(
+                "withdrawFees deferred by retry backoff: round={}",
+                current_round
+            );
+        } else if due && backup.is_some_and(|b| b.defer_locked("withdrawFees", reads)) {
+            sent = true;
+        } else if due {
+            let res = match confirm_quorum(quorum, orchestrator, reads, &expected).await {
+                Ok(()) => {
+                    withdraw_fees(
+                        bonding,
+                        orchestrator,
+                        current_round,
+                        pending_fees,
+                        funds,
+                        topup,
+                        split,
+                        reads.balance,
+                        sender,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(false) => {}
+                Ok(true) => {
+                    sent = true;
+                    sender.retries.withdraw_fees.succeeded();
+                }
+                Err(e) => {
+                    sent = true;
+                    sender
+                        .retries
+                        .withdraw_fees
+                        .failed(&sender.retries.policy, &e);
+                }
+            }
+        } else {
+            debug!(
+                "withdrawFees skipped: round={} pendingFeesWei={} < thresholdWei={}",
+                current_round, pending_fees, threshold
+            );
+        }
+    }
+
+    // Emit a single INFO snapshot when the locked-round values change (not every loop).
+    let snap = LockedSnapshot {
+        round: current_round,
+        pending_stake: pending_stake_for_snapshot.unwrap_or_else(U256::zero),
+        pending_fees: pending_fees_for_snapshot.unwrap_or_else(U256::zero),
+        stake_present: pending_stake_for_snapshot.is_some(),
+        fees_present: pending_fees_for_snapshot.is_some(),
+    };
+
+    if *last_locked_snapshot != Some(snap) {
+        info!(
+            event = "locked_snapshot",
+            round = %snap.round,
+            pending_stake_wei = snap.stake_present.then(|| snap.pending_stake.to_string()),
+            pending_fees_wei = snap.fees_present.then(|| snap.pending_fees.to_string()),
+            "locked snapshot changed"
+        );
+
+        *last_locked_snapshot = Some(snap);
+    } else {
+        debug!(
+            "locked snapshot unchanged: round={} stake_present={} fees_present={}",
+            snap.round, snap.stake_present, snap.fees_present
+        );
+    }
+
+    Ok(sent)
+}
+
+/// Ok without a quorum gate. The gate answers both actions from one check per block.
+async fn confirm_quorum(
+    quorum: Option<&QuorumGate>,
+    orchestrator: Address,
+    reads: &ChainReads,
+    expected: &LockedValues,
+) -> Result<(), AppError> {
+    match quorum {
+        Some(q) => q.confirm(orchestrator, reads.block, expected).await,
+        None => Ok(()),
+    }
+}
+
+/// Moves `transferable` bonded LPT to the LPT split, one transferBond per receiver. Unpaid
+/// legs of an earlier split go before a new one is planned, and only one is planned per round. Returns whether a tx was
+/// attempted; a pending leg from an earlier attempt also counts. The pool for the hints is
+/// read once at `block` and updated here as legs confirm.
+#[allow(clippy::too_many_arguments)]
+async fn transfer_bond<M: Middleware + 'static>(
+    bonding: &BondingManager<M>,
+    reader: &ChainReader<M>,
+    block: U64,
+    orchestrator: Address,
+    round: U256,
+    transferable: U256,
+    split: &Split,
+    sender: &mut TxSender,
+) -> Result<bool, AppError> {
+    let had_sent = sender
+        .payouts
+        .outstanding(TRANSFER_BOND)
+        .is_some_and(|p| p.legs.iter().any(|l| l.tx_hash.is_some()));
+    if !sender
+        .payouts
+        .settle(TRANSFER_BOND, bonding.client_ref())
+        .await?
+    {
+        return Ok(true);
+    }
+    // A leg from an earlier attempt was just settled. This loop's pendingStake and pool may
+    // have been read before it was mined, so the next loop decides with fresh reads.
+    if had_sent {
+        return Ok(true);
+    }
+    if let Some(plan) = sender.payouts.outstanding(TRANSFER_BOND)
+        && plan.remaining() > transferable
+    {
+        warn!(
+            "transferBond payout from round {} can't be finished: remaining_wei={} > transferable={}; dropping its unpaid legs",
+            plan.round,
+            plan.remaining(),
+            transferable
+        );
+        sender.payouts.abandon(TRANSFER_BOND);
+    }
+
+    if sender.payouts.outstanding(TRANSFER_BOND).is_none()
+        && !transferable.is_zero()
+        && !sender.payouts.planned_in(TRANSFER_BOND, round)
+    {
+        let amounts = split.amounts(transferable);
+        info!(
+            event = "payout_planned",
+            action = "transferBond",
+            round = %round,
+            amount_wei = %transferable,
+            legs = amounts.len(),
+            "transferBond payout planned"
+        );
+        sender.payouts.add(TRANSFER_BOND, round, amounts, false);
+    }
+
+    let mut pool = None;
+    if !sender.uses_node() && sender.payouts.next_leg(TRANSFER_BOND).is_some() {
+        pool = match reader.read_pool(block).await {
+            Ok(p) => Some(Pool::new(p)),
+            Err(e) => {
+                warn!("transcoder pool unavailable: {e}; sending transferBond without hints");
+                None
+            }
+        };
+    }
+
+    let mut attempted = false;
+    while let Some((leg, l)) = sender.payouts.next_leg(TRANSFER_BOND) {
+        if sender.stopping() {
+            break;
+        }
+        attempted = true;
+        let (hints, after) = match &pool {
+            Some(p) => match p
+                .transfer_to(bonding, orchestrator, l.to, l.amount, block)
+                .await
+            {
+                Ok((h, after)) => (h, Some(after)),
+                Err(e) => {
+                    warn!("transferBond hints unavailable: {e}; sending without");
+                    ([Address::zero(); 4], None)
+                }
+            },
+            None => ([Address::zero(); 4], None),
+        };
+        let before = sender.own_txs().latest();
+        match send_transfer_bond(bonding, orchestrator, round, l.to, l.amount, hints, sender).await
+        {
+            Ok(()) => {
+                sender.payouts.leg_done(TRANSFER_BOND, leg);
+                if after.is_some() {
+                    pool = after;
+                }
+            }
+            Err(e) => {
+                if let Some(tx_hash) = sender.own_txs().latest()
+                    && Some(tx_hash) != before
+                {
+                    sender.payouts.leg_sent(TRANSFER_BOND, leg, tx_hash);
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(attempted)
+}
+
+/// One transferBond call, signed here with pool hints for this leg.
+async fn send_transfer_bond<M: Middleware + 'static>(
+    bonding: &BondingManager<M>,
+    orchestrator: Address,
+    round: U256,
+    to: Address,
+    amount: U256,
+    hints: Hints,
+    sender: &mut TxSender,
+) -> Result<(), AppError> {
+    info!(
+        event = "transfer_bond_sending",
+        action = "transferBond",
+        round = %round,
+        from = ?orchestrator,
+        to = ?to,
+        amount_wei = %amount,
+        "transferBond sending"
+    );
+    if sender.uses_node() {
+        return Err(AppError::Chain(
+            ErrorKind::Config,
+            "transferBond is not available through the node executor".into(),
+        ));
+    }
+    let [old_prev, old_next, new_prev, new_next] = hints;
+    debug!("transferBond hints: old=({old_prev:?},{old_next:?}) new=({new_prev:?},{new_next:?})");
+    let call = bonding.transfer_bond(to, amount, old_prev, old_next, new_prev, new_next);
+    sender
+        .send_and_confirm(bonding.client_ref(), "transferBond", call, round)
+        .await
+}
+
+/// Pays out `pending_fees`: the signer's gas shortfall first, then the fee split, as legs of one
+/// payout. Unpaid legs of an earlier payout go before a new one is planned, and a new one only
+/// covers what they leave. Returns whether a tx was attempted; a pending leg from an earlier
+/// attempt also counts.
+#[allow(clippy::too_many_arguments)]
+async fn withdraw_fees<M: Middleware + 'static>(
+    bonding: &BondingManager<M>,
+    orchestrator: Address,
+    round: U256,
+    pending_fees: U256,
+    funds: Funds,
+    topup: Option<GasTopUp>,
+    split: &Split,
+    balance: Option<U256>,
+    sender: &mut TxSender,
+) -> Result<bool, AppError> {
+    let had_sent = sender
+        .payouts
+        .outstanding(WITHDRAW_FEES)
+        .is_some_and(|p| p.legs.iter().any(|l| l.tx_hash.is_some()));
+    if !sender
+        .payouts
+        .settle(WITHDRAW_FEES, bonding.client_ref())
+        .await?
+    {
+        return Ok(true);
+    }
+    // A leg from an earlier attempt was just settled. This loop's balance and pendingFees may
+    // have been read before it was mined, so the next loop decides with fresh reads.
+    if had_sent {
+        return Ok(true);
+    }
+    // The node pays only its own account, so legs for other receivers (e.g. from an earlier
+    // run with the signer executor) can't go through it.
+    if sender.uses_node()
+        && let Some(plan) = sender.payouts.outstanding(WITHDRAW_FEES)
+        && plan
+            .legs
+            .iter()
+            .any(|l| !l.done && split.receivers().all(|r| r != l.to))
+    {
+        warn!(
+            "withdrawFees payout from round {} has legs for other receivers than the node; dropping its unpaid legs",
+            plan.round
+        );
+        sender.payouts.abandon(WITHDRAW_FEES);
+    }
+    let reserved = match sender.payouts.outstanding(WITHDRAW_FEES) {
+        Some(plan) if plan.remaining() > pending_fees => {
+            warn!(
+                "withdrawFees payout from round {} can't be finished: remaining_wei={} > pendingFees={}; dropping its unpaid legs",
+                plan.round,
+                plan.remaining(),
+                pending_fees
+            );
+            sender.payouts.abandon(WITHDRAW_FEES);
+            U256::zero()
+        }
+        Some(plan) => plan.remaining(),
+        None => U256::zero(),
+    };
+    let mut available = pending_fees - reserved;
+
+    // The signer's shortfall comes out of the fees not promised to a receiver yet. It becomes
+    // the payout's first leg, so a top-up that is still unconfirmed is settled by its tx hash
+    // like any other leg instead of being worked out again.
+    if let Some(t) = topup
+        && !sender
+            .payouts
+            .outstanding(WITHDRAW_FEES)
+            .is_some_and(|p| p.has_unpaid_topup())
+    {
+        match balance {
+            Some(balance) => {
+                let amount = t.target.saturating_sub(balance).min(available);
+                if !amount.is_zero() {
+                    info!(
+                        "gas top-up: signer balance {} below target {}; withdrawing {} to the signer",
+                        balance, t.target, amount
+                    );
+                    sender
+                        .payouts
+                        .add(WITHDRAW_FEES, round, vec![(t.signer, amount)], true);
+                    available -= amount;
+                }
+            }
+            None => warn!("gas top-up skipped: signer balance was not read"),
+        }
+    }
+
+    if funds != Funds::Critical
+        && !sender
+            .payouts
+            .outstanding(WITHDRAW_FEES)
+            .is_some_and(|p| p.has_receiver_legs())
+        && !available.is_zero()
+    {
+        let amounts = split.amounts(available);
+        info!(
+            event = "payout_planned",
+            action = "withdrawFees",
+            round = %round,
+            amount_wei = %available,
+            legs = amounts.len(),
+            "withdrawFees payout planned"
+        );
+        sender.payouts.add(WITHDRAW_FEES, round, amounts, false);
+    }
+
+    let mut attempted = false;
+    while let Some((leg, l)) = sender.payouts.next_leg(WITHDRAW_FEES) {
+        if sender.stopping() {
+            break;
+        }
+        if funds == Funds::Critical && !l.topup {
+            debug!("withdrawFees to the receivers paused: signer balance critical");
+            break;
+        }
+        attempted = true;
+        let before = sender.own_txs().latest();
+        match send_withdraw_fees(bonding, orchestrator, round, l.to, l.amount, sender).await {
+            Ok(()) => sender.payouts.leg_done(WITHDRAW_FEES, leg),
+            Err(e) => {
+                // Broadcast but unconfirmed: its receipt decides before the leg is sent again.
+                if let Some(tx_hash) = sender.own_txs().latest()
+                    && Some(tx_hash) != before
+                {
+                    sender.payouts.leg_sent(WITHDRAW_FEES, leg, tx_hash);
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(attempted)
+}
+
+/// One withdrawFees call, signed here or requested from the node. The node always pays its own
+/// account, which startup checks is the only receiver.
+async fn send_withdraw_fees<M: Middleware + 'static>(
+    bonding: &BondingManager<M>,
+    orchestrator: Address,
+    round: U256,
+    to: Address,
+    amount: U256,
+    sender: &mut TxSender,
+) -> Result<(), AppError> {
+    info!(
+        event = "withdraw_fees_sending",
+        action = "withdrawFees",
+        round = %round,
+        from = ?orchestrator,
+        to = ?to,
+        amount_wei = %amount,
+        "withdrawFees sending"
+    );
+    if sender.uses_node() {
+        let action = NodeAction::WithdrawFees { amount };
+        sender.send_via_node(action, round).await
+    } else {
+        let call = bonding.withdraw_fees(to, amount);
+        sender
+            .send_and_confirm(bonding.client_ref(), "withdrawFees", call, round)
+            .await
+    }
+}
+
+/// Keystore + passphrase files.
+fn load_wallet(cfg: &Config) -> Result<LocalWallet, AppError> {
+    let passphrase_file = cfg
+        .passphrase_file
+        .as_deref()
+        .ok_or(AppError::MissingEnv("PASSPHRASE_FILE"))?;
+    let json_key_file = cfg
+        .json_key_file
+        .as_deref()
+        .ok_or(AppError::MissingEnv("JSON_KEY_FILE"))?;
+
+    let passphrase = std::fs::read_to_string(passphrase_file)
+        .map_err(|e| AppError::Wallet(format!("failed to read PASSPHRASE_FILE: {e}")))?;
+    let passphrase = passphrase.trim_end();
+
+    let wallet = LocalWallet::decrypt_keystore(Path::new(json_key_file), passphrase)
+        .map_err(|e| AppError::Wallet(format!("failed to decrypt JSON_KEY_FILE: {e}")))?
+        .with_chain_id(cfg.chain_id);
+    Ok(wallet)
+}
+
+fn validate_config(cfg: &Config) -> Result<(), AppError> {
+    // The go-livepeer CLI API has /reward and /withdrawFees (to the node's own account) only.
+    if cfg.node_cli_url.is_some() {
+        if cfg.enable_transfer_bond {
+            return Err(AppError::BadEnv(
+                "ENABLE_TRANSFER_BOND",
+                "must be false with EXECUTOR=node: the node API has no transferBond".into(),
+            ));
+        }
+        if cfg.gas_topup_target_wei.is_some() {
+            return Err(AppError::BadEnv(
+                "GAS_TOPUP_TARGET_WEI",
+                "not supported with EXECUTOR=node: the node withdraws fees to itself".into(),
+            ));
+        }
+        if cfg
+            .eth_fee_split
+            .as_ref()
+            .is_some_and(|s| s.receivers().count() > 1)
+        {
+            return Err(AppError::BadEnv(
+                "ETH_FEE_RECEIVERS",
+                "not supported with EXECUTOR=node: the node withdraws fees to itself".into(),
+            ));
+        }
+    }
+
+    if cfg.enable_transfer_bond {
+        if cfg.lpt_split.is_none() {
+            return Err(AppError::BadEnv(
+                "LPT_RECEIVER_ADDR",
+                "LPT_RECEIVER_ADDR or LPT_RECEIVERS required when ENABLE_TRANSFER_BOND=true".into(),
+            ));
+        }
+        if cfg.lpt_retain.is_none() {
+            return Err(AppError::BadEnv(
+                "LPT_RETAIN_POLICY",
+                "required when ENABLE_TRANSFER_BOND=true".into(),
+            ));
+        }
+    }
+
+    if let Some(ws) = &cfg.ws_rpc
+        && !ws.auth.headers.is_empty()
+    {
+        return Err(AppError::BadEnv(
+            "WS_RPC_URL",
+            "headers_file is not supported for WebSocket endpoints; use bearer_file or basic_auth_file".into(),
+        ));
+    }
+
+    if let Some(guard) = cfg.balance_guard
+        && guard.critical_cycles > guard.warn_cycles
+    {
+        return Err(AppError::BadEnv(
+            "LOW_BALANCE_CRITICAL_CYCLES",
+            format!(
+                "must not exceed LOW_BALANCE_WARN_CYCLES ({})",
+                guard.warn_cycles
+            ),
+        ));
+    }
+
+    if cfg.reward_watch.log_chunk_blocks == 0 {
+        return Err(AppError::BadEnv(
+            "GET_LOGS_MAX_BLOCKS",
+            "must be at least 1".into(),
+        ));
+    }
+
+    if cfg.retry_max_attempts == 0 {
+        return Err(AppError::BadEnv(
+            "RETRY_MAX_ATTEMPTS",
+            "must be at least 1".into(),
+        ));
+    }
+
+    if let Some(dir) = &cfg.state_dir
+        && !dir.is_dir()
+    {
+        return Err(AppError::BadEnv(
+            "STATE_DIR",
+            format!("not a directory: {}", dir.display()),
+        ));
+    }
+
+    if let Some(path) = &cfg.lease_file
+        && !path
+            .parent()
+            .is_some_and(|d| d.as_os_str().is_empty() || d.is_dir())
+    {
+        return Err(AppError::BadEnv(
+            "LEASE_FILE",
+            format!("parent directory does not exist: {}", path.display()),
+        ));
+    }
+
+    if cfg.lease_ttl_secs < 3 {
+        return Err(AppError::BadEnv(
+            "LEASE_TTL_SECS",
+            "must be at least 3".into(),
+        ));
+    }
+
+    if cfg.health_max_missed_loops == 0 {
+        return Err(AppError::BadEnv(
+            "HEALTH_MAX_MISSED_LOOPS",
+            "must be at least 1".into(),
+        ));
+    }
+
+    if let Some(min) = cfg.quorum_min
+        && (min == 0 || min > cfg.quorum_rpc_endpoints.len())
+    {
+        return Err(AppError::BadEnv(
+            "QUORUM_MIN",
+            format!(
+                "must be between 1 and the number of quorum providers ({})",
+                cfg.quorum_rpc_endpoints.len()
+            ),
+        ));
+    }
+
+    if cfg.enable_withdraw_fees {
+        if cfg.eth_fee_split.is_none() {
+            return Err(AppError::BadEnv(
+                "ETH_FEE_RECEIVER_ADDR",
+                "ETH_FEE_RECEIVER_ADDR or ETH_FEE_RECEIVERS required when ENABLE_WITHDRAW_FEES=true"
+                    .into(),
+            ));
+        }
+        if cfg.eth_fee_withdraw_threshold_wei.is_none() {
+            return Err(AppError::BadEnv(
+                "ETH_FEE_WITHDRAW_THRESHOLD_WEI",
+                "required when ENABLE_WITHDRAW_FEES=true".into(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn load_config() -> Result<Config, AppError> {
+    // feature flags: default to current behavior (enabled) if not specified
+    let enable_reward = parse_env_bool_opt("ENABLE_REWARD")?.unwrap_or(true);
+    let enable_transfer_bond = parse_env_bool_opt("ENABLE_TRANSFER_BOND")?.unwrap_or(true);
+    let enable_withdraw_fees = parse_env_bool_opt("ENABLE_WITHDRAW_FEES")?.unwrap_or(true);
+    let missed_reward_check = parse_env_bool_opt("MISSED_REWARD_CHECK")?.unwrap_or(false);
+    let reward_watch = RewardWatchConfig {
+        lookback_blocks: parse_env_u64_opt("REWARD_HISTORY_LOOKBACK_BLOCKS")?.unwrap_or(1_000_000),
+        log_chunk_blocks: parse_env_u64_opt("GET_LOGS_MAX_BLOCKS")?.unwrap_or(50_000),
+    };
+
+    // HTTP_RPC_URLS (comma-separated, `url[;priority=N]`) takes precedence over HTTP_RPC_URL.
+    let http_rpc_endpoints = match env::var("HTTP_RPC_URLS") {
+        Ok(raw) => {
+            rpc::parse_endpoint_specs(&raw).map_err(|e| AppError::BadEnv("HTTP_RPC_URLS", e))?
+        }
+        Err(_) => rpc::parse_endpoint_specs(&must_env("HTTP_RPC_URL")?)
+            .map_err(|e| AppError::BadEnv("HTTP_RPC_URL", e))?,
+    };
+    // Same `url[;option=...]` syntax; credentials are sent with the WebSocket handshake.
+    let ws_rpc = match env::var("WS_RPC_URL") {
+        Ok(raw) if !raw.trim().is_empty() => {
+            let mut specs =
+                rpc::parse_endpoint_specs(&raw).map_err(|e| AppError::BadEnv("WS_RPC_URL", e))?;
+            if specs.len() != 1 {
+                return Err(AppError::BadEnv(
+                    "WS_RPC_URL",
+                    "expected a single endpoint".into(),
+                ));
+            }
+            Some(specs.remove(0))
+        }
+        _ => None,
+    };
+    let broadcast_rpc_endpoints = match env::var("BROADCAST_RPC_URLS") {
+        Ok(raw) if !raw.trim().is_empty() => Some(
+            rpc::parse_endpoint_specs(&raw)
+                .map_err(|e| AppError::BadEnv("BROADCAST_RPC_URLS", e))?,
+        ),
+        _ => None,
+    };
+    let broadcast_fan_out = parse_env_bool_opt("BROADCAST_FAN_OUT")?.unwrap_or(false);
+    let default_timeouts = RpcTimeouts::default();
+    let rpc_timeouts = RpcTimeouts {
+        read: parse_env_secs_opt("RPC_READ_TIMEOUT_SECS")?.unwrap_or(default_timeouts.read),
+        estimate: parse_env_secs_opt("RPC_ESTIMATE_TIMEOUT_SECS")?
+            .unwrap_or(default_timeouts.estimate),
+        broadcast: parse_env_secs_opt("RPC_BROADCAST_TIMEOUT_SECS")?
+            .unwrap_or(default_timeouts.broadcast),
+    };
+    let chain_id = must_parse_env_u64("CHAIN_ID")?;
+
+    let rounds_manager_addr = must_parse_env_addr("ROUNDS_MANAGER_ADDR")?;
+    let bonding_manager_addr = must_parse_env_addr("BONDING_MANAGER_ADDR")?;
+
+    let node_cli_url = match env::var("EXECUTOR") {
+        Ok(raw) => match raw.trim().to_ascii_lowercase().as_str() {
+            "signer" => None,
+            "node" => {
+                let raw = env::var("NODE_CLI_URL").unwrap_or_else(|_| DEFAULT_NODE_CLI_URL.into());
+                Some(
+                    Url::parse(raw.trim())
+                        .map_err(|e| AppError::BadEnv("NODE_CLI_URL", format!("{e}")))?,
+                )
+            }
+            _ => {
+                return Err(AppError::BadEnv(
+                    "EXECUTOR",
+                    "expected one of: signer, node".into(),
+                ));
+            }
+        },
+        Err(_) => None,
+    };
+    let node_cli_timeout_secs = parse_env_u64_opt("NODE_CLI_TIMEOUT_SECS")?.unwrap_or(180);
+    let (json_key_file, passphrase_file) = if node_cli_url.is_none() {
+        (
+            Some(must_env("JSON_KEY_FILE")?),
+            Some(must_env("PASSPHRASE_FILE")?),
+        )
+    } else {
+        (None, None)
+    };
+    let orchestrator_addr = parse_env_addr_opt("ORCHESTRATOR_ADDR")?;
+
+    let quorum_min = parse_env_u64_opt("QUORUM_MIN")?.map(|m| m as usize);
+    // Quorum providers default to the HTTP endpoints, each queried on its own.
+    let quorum_rpc_endpoints = match env::var("QUORUM_RPC_URLS") {
+        Ok(raw) => {
+            rpc::parse_endpoint_specs(&raw).map_err(|e| AppError::BadEnv("QUORUM_RPC_URLS", e))?
+        }
+        Err(_) => http_rpc_endpoints.clone(),
+    };
+
+    let enable_multicall = parse_env_bool_opt("ENABLE_MULTICALL")?.unwrap_or(true);
+    let multicall_addr = parse_env_addr_opt("MULTICALL_ADDR")?;
+
+    let loop_sleep_secs = parse_env_u64_opt("LOOP_SLEEP_SECS")?.unwrap_or(6);
+    let scheduler_mode = match env::var("SCHEDULER_MODE") {
+        Ok(raw) => SchedulerMode::parse(&raw).ok_or_else(|| {
+            AppError::BadEnv("SCHEDULER_MODE", "expected one of: fixed, block".into())
+        })?,
+        Err(_) => SchedulerMode::Fixed,
+    };
+    let l1_block_time_secs = parse_env_u64_opt("L1_BLOCK_TIME_SECS")?.unwrap_or(12);
+    let backup = match env::var("KEEPER_MODE") {
+        Ok(raw) => match raw.trim().to_ascii_lowercase().as_str() {
+            "primary" => None,
+            "backup" => Some(BackupPolicy {
+                reward_delay_blocks: parse_env_u64_opt("BACKUP_REWARD_DELAY_BLOCKS")?
+                    .unwrap_or(300),
+                locked_delay_blocks: parse_env_u64_opt("BACKUP_LOCKED_DELAY_BLOCKS")?
+                    .unwrap_or(200),
+            }),
+            _ => {
+                return Err(AppError::BadEnv(
+                    "KEEPER_MODE",
+                    "expected one of: primary, backup".into(),
+                ));
+            }
+        },
+        Err(_) => None,
+    };
+    let wake_margin_secs = parse_env_u64_opt("PHASE_WAKE_MARGIN_SECS")?.unwrap_or(60);
+    let max_sleep_secs = parse_env_u64_opt("MAX_SLEEP_SECS")?.unwrap_or(1800);
+    let receipt_timeout_secs = parse_env_u64_opt("RECEIPT_TIMEOUT_SECS")?.unwrap_or(90);
+    let retry_base_secs = parse_env_u64_opt("RETRY_BASE_SECS")?.unwrap_or(loop_sleep_secs);
+    let retry_max_secs = parse_env_u64_opt("RETRY_MAX_SECS")?.unwrap_or(900);
+    let retry_max_attempts = parse_env_u64_opt("RETRY_MAX_ATTEMPTS")?.unwrap_or(5) as u32;
+    let state_dir = env::var("STATE_DIR")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .map(PathBuf::from);
+    let shutdown_grace_secs = parse_env_u64_opt("SHUTDOWN_GRACE_SECS")?.unwrap_or(30);
+    let lease_file = env::var("LEASE_FILE")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .map(PathBuf::from);
+    let lease_ttl_secs = parse_env_u64_opt("LEASE_TTL_SECS")?.unwrap_or(60);
+    let metrics_addr = match env::var("METRICS_ADDR") {
+        Ok(raw) if !raw.trim().is_empty() => Some(raw.trim().parse().map_err(|e| {
+            AppError::BadEnv(
+                "METRICS_ADDR",
+                format!("expected host:port, e.g. 0.0.0.0:9464: {e}"),
+            )
+        })?),
+        _ => None,
+    };
+    let health_max_missed_loops = parse_env_u64_opt("HEALTH_MAX_MISSED_LOOPS")?.unwrap_or(3) as u32;
+    let balance_guard = if parse_env_bool_opt("ENABLE_BALANCE_GUARD")?.unwrap_or(false) {
+        Some(BalanceGuardConfig {
+            warn_cycles: parse_env_u64_opt("LOW_BALANCE_WARN_CYCLES")?.unwrap_or(10),
+            critical_cycles: parse_env_u64_opt("LOW_BALANCE_CRITICAL_CYCLES")?.unwrap_or(2),
+        })
+    } else {
+        None
+    };
+
+    // LPT_RECEIVERS (same format as ETH_FEE_RECEIVERS) takes precedence over LPT_RECEIVER_ADDR.
+    let (lpt_split, lpt_retain) = if enable_transfer_bond {
+        let split = match env::var("LPT_RECEIVERS") {
+            Ok(raw) if !raw.trim().is_empty() => {
+                Split::parse(&raw).map_err(|e| AppError::BadEnv("LPT_RECEIVERS", e))?
+            }
+            _ => Split::single(must_parse_env_addr("LPT_RECEIVER_ADDR")?),
+        };
+        // LPT_MIN_RETAIN_WEI is the amount itself for `fixed`, and a floor for the shares.
+        let share_bps = || -> Result<u32, AppError> {
+            match must_parse_env_u64("LPT_RETAIN_BPS")? {
+                bps @ 1..=10_000 => Ok(bps as u32),
+                _ => Err(AppError::BadEnv(
+                    "LPT_RETAIN_BPS",
+                    "must be between 1 and 10000".into(),
+                )),
+            }
+        };
+        let min = || -> Result<U256, AppError> {
+            Ok(parse_env_u256_opt("LPT_MIN_RETAIN_WEI")?.unwrap_or_default())
+        };
+        let retain = match env::var("LPT_RETAIN_POLICY") {
+            Ok(raw) => match raw.trim().to_ascii_lowercase().as_str() {
+                "fixed" => RetainPolicy::Fixed(must_parse_env_u256("LPT_MIN_RETAIN_WEI")?),
+                "pending_share" => RetainPolicy::PendingShare {
+                    bps: share_bps()?,
+                    min: min()?,
+                },
+                "stake_share" => RetainPolicy::StakeShare {
+                    bps: share_bps()?,
+                    min: min()?,
+                },
+                _ => {
+                    return Err(AppError::BadEnv(
+                        "LPT_RETAIN_POLICY",
+                        "expected one of: fixed, pending_share, stake_share".into(),
+                    ));
+                }
+            },
+            Err(_) => RetainPolicy::Fixed(must_parse_env_u256("LPT_MIN_RETAIN_WEI")?),
+        };
+        (Some(split), Some(retain))
+    } else {
+        (None, None)
+    };
+
+    // ETH_FEE_RECEIVERS (comma-separated, `address;bps=N[;dust]`) takes precedence over
+    // ETH_FEE_RECEIVER_ADDR.
+    let (eth_fee_split, eth_fee_withdraw_threshold_wei) = if enable_withdraw_fees {
+        let split = match env::var("ETH_FEE_RECEIVERS") {
+            Ok(raw) if !raw.trim().is_empty() => {
+                Split::parse(&raw).map_err(|e| AppError::BadEnv("ETH_FEE_RECEIVERS", e))?
+            }
+            _ => Split::single(must_parse_env_addr("ETH_FEE_RECEIVER_ADDR")?),
+        };
+        (
+            Some(split),
+            Some(must_parse_env_u256("ETH_FEE_WITHDRAW_THRESHOLD_WEI")?),
+        )
+    } else {
+        (None, None)
+    };
+    let gas_topup_target_wei = if enable_withdraw_fees {
+        parse_env_u256_opt("GAS_TOPUP_TARGET_WEI")?.filter(|t| !t.is_zero())
+    } else {
+        None
+    };
+
+    Ok(Config {
+        http_rpc_endpoints,
+        ws_rpc,
+        broadcast_rpc_endpoints,
+        broadcast_fan_out,
+        rpc_timeouts,
+        chain_id,
+        rounds_manager_addr,
+        bonding_manager_addr,
+        json_key_file,
+        passphrase_file,
+        orchestrator_addr,
+        quorum_rpc_endpoints,
+        quorum_min,
+        enable_multicall,
+        multicall_addr,
+        loop_sleep_secs,
+        scheduler_mode,
+        l1_block_time_secs,
+        wake_margin_secs,
+        max_sleep_secs,
+        receipt_timeout_secs,
+        retry_base_secs,
+        retry_max_secs,
+        retry_max_attempts,
+        state_dir,
+        shutdown_grace_secs,
+        lease_file,
+        lease_ttl_secs,
+        node_cli_url,
+        node_cli_timeout_secs,
+        backup,
+        metrics_addr,
+        health_max_missed_loops,
+        balance_guard,
+        enable_reward,
+        missed_reward_check,
+        reward_watch,
+        enable_transfer_bond,
+        enable_withdraw_fees,
+        lpt_split,
+        lpt_retain,
+        eth_fee_split,
+        eth_fee_withdraw_threshold_wei,
+        gas_topup_target_wei,
+    })
+}
+
+fn must_env(key: &'static str) -> Result<String, AppError> {
+    env::var(key).map_err(|_| AppError::MissingEnv(key))
+}
+
+fn parse_env_u64_opt(key: &'static str) -> Result<Option<u64>, AppError> {
+    match env::var(key) {
+        Ok(s) => {
+            let v = s
+                .parse::<u64>()
+                .map_err(|e| AppError::BadEnv(key, format!("{e}")))?;
+            Ok(Some(v))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Whole or fractional seconds, e.g. `10` or `2.5`.
+fn parse_env_secs_opt(key: &'static str) -> Result<Option<Duration>, AppError> {
+    match env::var(key) {
+        Ok(s) => {
+            let secs = s
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| AppError::BadEnv(key, format!("{e}")))?;
+            if !secs.is_finite() || secs <= 0.0 {
+                return Err(AppError::BadEnv(key, "must be a positive number".into()));
+            }
+            Ok(Some(Duration::from_secs_f64(secs)))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+fn must_parse_env_u64(key: &'static str) -> Result<u64, AppError> {
+    let s = must_env(key)?;
+    s.parse::<u64>()
+        .map_err(|e| AppError::BadEnv(key, format!("{e}")))
+}
+
+fn parse_env_addr_opt(key: &'static str) -> Result<Option<Address>, AppError> {
+    match env::var(key) {
+        Ok(s) => {
+            let a = s
+                .parse::<Address>()
+                .map_err(|e| AppError::BadEnv(key, format!("{e}")))?;
+            Ok(Some(a))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+fn must_parse_env_addr(key: &'static str) -> Result<Address, AppError> {
+    let s = must_env(key)?;
+    s.parse::<Address>()
+        .map_err(|e| AppError::BadEnv(key, format!("{e}")))
+}
+
+fn parse_env_u256_opt(key: &'static str) -> Result<Option<U256>, AppError> {
+    match env::var(key) {
+        Ok(s) if !s.trim().is_empty() => U256::from_dec_str(s.trim())
+            .map(Some)
+            .map_err(|e| AppError::BadEnv(key, format!("{e}"))),
+        _ => Ok(None),
+    }
+}
+
+fn must_parse_env_u256(key: &'static str) -> Result<U256, AppError> {
+    let s = must_env(key)?;
+    // Accept decimal strings
+    U256::from_dec_str(&s).map_err(|e| AppError::BadEnv(key, format!("{e}")))
+}
+
+fn parse_env_bool_opt(key: &'static str) -> Result<Option<bool>, AppError> {
+    match env::var(key) {
+        Ok(raw) => {
+            let v = match raw.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "y" | "on" => true,
+                "0" | "false" | "no" | "n" | "off" => false,
+                _ => {
+                    return Err(AppError::BadEnv(
+                        key,
+                        "expected boolean (true/false)".into(),
+                    ));
+                }
+            };
+            Ok(Some(v))
+        }
+        Err(_) => Ok(None),
+    }
+}
@@ -0,0 +1,418 @@
+//! Prometheus metrics, served as text on `/metrics` when `METRICS_ADDR` is set.
+//!
+//! Values are kept here as plain numbers and rendered on scrape: the latest chain reads, tx
+//! outcomes and gas by action, and the per-endpoint RPC health the failover transport already
+//! tracks. Wei amounts are exported as floats, which is precise enough for dashboards and
+//! alerts but not for accounting.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ethers::types::U256;
+
+use crate::{reads::ChainReads, rpc::EndpointHealth};
+
+/// How a tx (or node request) for an action ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TxOutcome {
+    Confirmed,
+    Reverted,
+    /// Rejected before it was broadcast (estimation, nonce, RPC error).
+    SendFailed,
+    /// Broadcast, but no receipt within the timeout.
+    Unconfirmed,
+    /// Cut short by shutdown.
+    Interrupted,
+}
+
+impl TxOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            TxOutcome::Confirmed => "confirmed",
+            TxOutcome::Reverted => "reverted",
+            TxOutcome::SendFailed => "send_failed",
+            TxOutcome::Unconfirmed => "unconfirmed",
+            TxOutcome::Interrupted => "interrupted",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    reads: Option<ChainReads>,
+    read_failures: u64,
+    txs: BTreeMap<(String, TxOutcome), u64>,
+    gas_used: BTreeMap<String, f64>,
+    gas_spent_wei: BTreeMap<String, f64>,
+    last_gas_used: BTreeMap<String, U256>,
+    runway_cycles: Option<f64>,
+    missed_rewards: u64,
+    missed_reward_loss_wei: f64,
+    /// Unix seconds.
+    last_loop: Option<u64>,
+}
+
+impl Metrics {
+    fn inner(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn observe_reads(&self, reads: &ChainReads) {
+        self.inner().reads = Some(*reads);
+    }
+
+    pub fn read_failed(&self) {
+        self.inner().read_failures += 1;
+    }
+
+    pub fn tx_outcome(&self, action: &str, outcome: TxOutcome) {
+        *self
+            .inner()
+            .txs
+            .entry((action.to_owned(), outcome))
+            .or_default() += 1;
+    }
+
+    /// Gas of a mined tx, reverted or not.
+    pub fn gas(&self, action: &str, gas_used: Option<U256>, gas_price: Option<U256>) {
+        let Some(gas_used) = gas_used else {
+            return;
+        };
+        let mut inner = self.inner();
+        *inner.gas_used.entry(action.to_owned()).or_default() += wei_f64(gas_used);
+        inner.last_gas_used.insert(action.to_owned(), gas_used);
+        if let Some(price) = gas_price {
+            *inner.gas_spent_wei.entry(action.to_owned()).or_default() +=
+                wei_f64(gas_used.saturating_mul(price));
+        }
+    }
+
+    /// Gas used by the latest mined tx for `action`.
+    pub fn last_gas_used(&self, action: &str) -> Option<U256> {
+        self.inner().last_gas_used.get(action).copied()
+    }
+
+    pub fn runway(&self, cycles: f64) {
+        self.inner().runway_cycles = Some(cycles);
+    }
+
+    /// `estimated_loss` is unknown until a `Reward` event has been seen.
+    pub fn rewards_missed(&self, rounds: u64, estimated_loss: Option<U256>) {
+        let mut inner = self.inner();
+        inner.missed_rewards += rounds;
+        if let Some(wei) = estimated_loss {
+            inner.missed_reward_loss_wei += wei_f64(wei);
+        }
+    }
+
+    /// The loop read chain state and went through its actions.
+    pub fn loop_completed(&self) {
+        self.inner().last_loop = Some(unix_now());
+    }
+
+    pub fn render(&self, rpc: &[(String, EndpointHealth)]) -> String {
+        let inner = self.inner();
+        let mut out = String::new();
+
+        if let Some(r) = &inner.reads {
+            gauge(
+                &mut out,
+                "round",
+                "Current protocol round.",
+                wei_f64(r.state.round),
+            );
+            gauge(
+                &mut out,
+                "round_initialized",
+                "1 if the current round is initialized.",
+                flag(r.state.initialized),
+            );
+            gauge(
+                &mut out,
+                "round_locked",
+                "1 if the current round is in its lock window.",
+                flag(r.state.locked),
+            );
+            gauge(
+                &mut out,
+                "read_block",
+                "L2 block of the latest reads.",
+                r.block.as_u64() as f64,
+            );
+            if let Some(lrr) = r.last_reward_round {
+                gauge(
+                    &mut out,
+                    "last_reward_round",
+                    "lastRewardRound of the orchestrator.",
+                    wei_f64(lrr),
+                );
+                gauge(
+                    &mut out,
+                    "rounds_since_reward",
+                    "Rounds since the orchestrator last called reward().",
+                    wei_f64(r.state.round.saturating_sub(lrr)),
+                );
+            }
+            if let Some(v) = r.pending_stake {
+                gauge(
+                    &mut out,
+                    "pending_stake_wei",
+                    "pendingStake of the orchestrator.",
+                    wei_f64(v),
+                );
+            }
+            if let Some(v) = r.pending_fees {
+                gauge(
+                    &mut out,
+                    "pending_fees_wei",
+                    "pendingFees of the orchestrator.",
+                    wei_f64(v),
+                );
+            }
+            if let Some(v) = r.balance {
+                gauge(
+                    &mut out,
+                    "signer_balance_wei",
+                    "ETH balance of the signer.",
+                    wei_f64(v),
+                );
+            }
+        }
+
+        if let Some(cycles) = inner.runway_cycles {
+            gauge(
+                &mut out,
+                "signer_runway_cycles",
+                "Rounds of the enabled actions the signer balance pays for.",
+                cycles,
+            );
+        }
+
+        header(
+            &mut out,
+            "chain_read_failures_total",
+            "counter",
+            "Loops whose chain reads failed.",
+        );
+        sample(
+            &mut out,
+            "chain_read_failures_total",
+            &[],
+            inner.read_failures as f64,
+        );
+
+        if let Some(ts) = inner.last_loop {
+            gauge(
+                &mut out,
+                "last_loop_success_timestamp_seconds",
+                "Unix time the last loop completed.",
+                ts as f64,
+            );
+        }
+
+        header(
+            &mut out,
+            "txs_total",
+            "counter",
+            "Txs by action and outcome.",
+        );
+        for ((action, outcome), n) in &inner.txs {
+            sample(
+                &mut out,
+                "txs_total",
+                &[("action", action), ("outcome", outcome.as_str())],
+                *n as f64,
+            );
+        }
+        header(
+            &mut out,
+            "tx_gas_used_total",
+            "counter",
+            "Gas used by mined txs.",
+        );
+        for (action, gas) in &inner.gas_used {
+            sample(&mut out, "tx_gas_used_total", &[("action", action)], *gas);
+        }
+        header(
+            &mut out,
+            "tx_gas_spent_wei_total",
+            "counter",
+            "gasUsed * effectiveGasPrice of mined txs.",
+        );
+        for (action, wei) in &inner.gas_spent_wei {
+            sample(
+                &mut out,
+                "tx_gas_spent_wei_total",
+                &[("action", action)],
+                *wei,
+            );
+        }
+
+        header(
+            &mut out,
+            "missed_rewards_total",
+            "counter",
+            "Rounds the orchestrator was active in that closed without reward().",
+        );
+        sample(
+            &mut out,
+            "missed_rewards_total",
+            &[],
+            inner.missed_rewards as f64,
+        );
+        header(
+            &mut out,
+            "missed_reward_estimated_loss_wei_total",
+            "counter",
+            "Estimated LPT lost to missed rounds, from the latest Reward amount.",
+        );
+        sample(
+            &mut out,
+            "missed_reward_estimated_loss_wei_total",
+            &[],
+            inner.missed_reward_loss_wei,
+        );
+
+        rpc_metrics(&mut out, rpc);
+        out
+    }
+}
+
+fn rpc_metrics(out: &mut String, rpc: &[(String, EndpointHealth)]) {
+    per_endpoint(
+        out,
+        rpc,
+        (
+            "rpc_requests_total",
+            "counter",
+            "RPC requests per endpoint.",
+        ),
+        |h| Some(h.requests as f64),
+    );
+    per_endpoint(
+        out,
+        rpc,
+        (
+            "rpc_failures_total",
+            "counter",
+            "Failed RPC requests per endpoint.",
+        ),
+        |h| Some(h.failures as f64),
+    );
+    per_endpoint(
+        out,
+        rpc,
+        (
+            "rpc_timeouts_total",
+            "counter",
+            "RPC requests that timed out without a response.",
+        ),
+        |h| Some(h.hung as f64),
+    );
+    per_endpoint(
+        out,
+        rpc,
+        (
+            "rpc_last_latency_seconds",
+            "gauge",
+            "Latency of the last successful RPC request.",
+        ),
+        |h| h.last_latency.map(|d| d.as_secs_f64()),
+    );
+    per_endpoint(
+        out,
+        rpc,
+        (
+            "rpc_endpoint_score",
+            "gauge",
+            "Endpoint health score, 1 = recent requests all succeeded.",
+        ),
+        |h| Some(h.score),
+    );
+
+    // Successful requests only; failures are counted above.
+    let name = "rpc_request_duration_seconds";
+    header(out, name, "summary", "Latency of successful RPC requests.");
+    for (label, h) in rpc {
+        let labels = [("endpoint", label.as_str())];
+        sample(
+            out,
+            &format!("{name}_sum"),
+            &labels,
+            h.latency_total.as_secs_f64(),
+        );
+        sample(
+            out,
+            &format!("{name}_count"),
+            &labels,
+            h.requests.saturating_sub(h.failures) as f64,
+        );
+    }
+}
+
+/// `(name, type, help)` plus one sample per endpoint.
+fn per_endpoint(
+    out: &mut String,
+    rpc: &[(String, EndpointHealth)],
+    (name, kind, help): (&str, &str, &str),
+    value: impl Fn(&EndpointHealth) -> Option<f64>,
+) {
+    header(out, name, kind, help);
+    for (label, h) in rpc {
+        if let Some(v) = value(h) {
+            sample(out, name, &[("endpoint", label)], v);
+        }
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    header(out, name, "gauge", help);
+    sample(out, name, &[], value);
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP funds_transfer_{name} {help}");
+    let _ = writeln!(out, "# TYPE funds_transfer_{name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    let _ = write!(out, "funds_transfer_{name}");
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| format!("{k}=\"{}\"", escape(v)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+fn escape(v: &str) -> String {
+    v.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn flag(b: bool) -> f64 {
+    if b { 1.0 } else { 0.0 }
+}
+
+fn wei_f64(v: U256) -> f64 {
+    v.to_string().parse().unwrap_or(f64::NAN)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
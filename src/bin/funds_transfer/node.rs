@@ -0,0 +1,227 @@
+//! Executor backend that drives a local go-livepeer node over its CLI web server.
+//!
+//! With `EXECUTOR=node` this service holds no key. Decisions are still made here from chain
+//! reads, but each action becomes a request to the node's CLI API (`NODE_CLI_URL`, by default
+//! `http://127.0.0.1:7935`). The node signs with its own wallet and answers once the tx is mined.
+//!
+//! The CLI API only covers part of what the signer executor does: `/reward`, and
+//! `/withdrawFees` with an `amount`, which always pays the node's own account. It has no
+//! transferBond endpoint. `validate_config` and startup reject the other combinations.
+
+use std::time::Duration;
+
+use ethers::types::{Address, U256};
+use reqwest::Method;
+use tracing::info;
+use url::Url;
+
+use crate::{AppError, retry::ErrorKind, rpc::redact_url};
+
+/// Longest response body kept in logs and errors.
+const BODY_LOG_LIMIT: usize = 200;
+
+#[derive(Clone, Debug)]
+pub enum NodeAction {
+    Reward,
+    /// To the node's own account; the API takes no receiver.
+    WithdrawFees {
+        amount: U256,
+    },
+}
+
+impl NodeAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeAction::Reward => "reward",
+            NodeAction::WithdrawFees { .. } => "withdrawFees",
+        }
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            NodeAction::Reward => "/reward",
+            NodeAction::WithdrawFees { .. } => "/withdrawFees",
+        }
+    }
+
+    fn form(&self) -> Vec<(&'static str, String)> {
+        match self {
+            NodeAction::Reward => Vec::new(),
+            NodeAction::WithdrawFees { amount } => vec![("amount", amount.to_string())],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NodeClient {
+    base: Url,
+    http: reqwest::Client,
+}
+
+impl NodeClient {
+    /// `timeout` covers the whole request, including the node waiting for the tx to be mined.
+    pub fn new(base: Url, timeout: Duration) -> Result<Self, AppError> {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| AppError::Chain(ErrorKind::Config, format!("node http client: {e}")))?;
+        Ok(Self { base, http })
+    }
+
+    pub fn describe(&self) -> String {
+        redact_url(&self.base)
+    }
+
+    /// `GET /status`; fails if the node is unreachable or not a go-livepeer CLI server.
+    pub async fn status(&self) -> Result<serde_json::Value, AppError> {
+        let body = self.request(Method::GET, "/status", &[]).await?;
+        serde_json::from_str(&body).map_err(|e| {
+            AppError::Chain(
+                ErrorKind::Config,
+                format!("node /status returned invalid JSON: {e}"),
+            )
+        })
+    }
+
+    /// `GET /ethAddr`: the address the node signs with.
+    pub async fn eth_addr(&self) -> Result<Address, AppError> {
+        let body = self.request(Method::GET, "/ethAddr", &[]).await?;
+        body.trim().trim_matches('"').parse().map_err(|e| {
+            AppError::Chain(
+                ErrorKind::Config,
+                format!(
+                    "node /ethAddr returned an invalid address {:?}: {e}",
+                    truncate(&body)
+                ),
+            )
+        })
+    }
+
+    pub async fn execute(&self, action: &NodeAction, round: U256) -> Result<(), AppError> {
+        let name = action.name();
+        info!(
+            event = "node_requested",
+            action = name,
+            round = %round,
+            node = %self.describe(),
+            path = action.path(),
+            "{name} requested from node"
+        );
+        let body = self
+            .request(Method::POST, action.path(), &action.form())
+            .await?;
+        info!(
+            event = "node_confirmed",
+            action = name,
+            round = %round,
+            status = "confirmed",
+            response = truncate(body.trim()),
+            "{name} confirmed by node"
+        );
+        Ok(())
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        form: &[(&'static str, String)],
+    ) -> Result<String, AppError> {
+        let what = format!("node {method} {path}");
+        let url = self.base.join(path).map_err(|e| {
+            AppError::Chain(ErrorKind::Config, format!("{what}: bad NODE_CLI_URL: {e}"))
+        })?;
+
+        let mut req = self.http.request(method, url);
+        if !form.is_empty() {
+            req = req.form(form);
+        }
+        let resp = req.send().await.map_err(|e| {
+            AppError::Chain(
+                ErrorKind::Transport,
+                format!("{what} failed: {}", e.without_url()),
+            )
+        })?;
+
+        let status = resp.status();
+        let body = resp.text().await.map_err(|e| {
+            AppError::Chain(
+                ErrorKind::Transport,
+                format!("{what} failed: {}", e.without_url()),
+            )
+        })?;
+        if !status.is_success() {
+            return Err(AppError::node(
+                &what,
+                status.as_u16(),
+                truncate(body.trim()),
+            ));
+        }
+        Ok(body)
+    }
+}
+
+fn truncate(s: &str) -> &str {
+    match s.char_indices().nth(BODY_LOG_LIMIT) {
+        Some((i, _)) => &s[..i],
+        None => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use super::*;
+    use crate::node_stub::{self, Stub};
+
+    fn start(stub: Stub) -> NodeClient {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        thread::spawn(move || node_stub::serve(listener, stub));
+        NodeClient::new(url, Duration::from_secs(5)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn drives_the_node_cli_api() {
+        let node = start(Stub::default());
+        node.status().await.unwrap();
+        assert_eq!(
+            node.eth_addr().await.unwrap(),
+            "0x193e8407c04dd802849fc3f24156b8eed6635819"
+                .parse::<Address>()
+                .unwrap()
+        );
+        node.execute(&NodeAction::Reward, U256::one())
+            .await
+            .unwrap();
+        node.execute(
+            &NodeAction::WithdrawFees {
+                amount: U256::exp10(17),
+            },
+            U256::one(),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn classifies_node_failures() {
+        let node = start(Stub {
+            fail: Some("/reward=500:execution reverted: already rewarded".into()),
+            ..Stub::default()
+        });
+        let err = node
+            .execute(&NodeAction::Reward, U256::one())
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Revert);
+
+        // Endpoints the node doesn't have are config errors, not retried.
+        let err = node
+            .request(Method::POST, "/transferBond", &[("amount", "1".into())])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config);
+    }
+}
@@ -0,0 +1,118 @@
+//! Stand-in for the go-livepeer CLI web server, for the node executor tests.
+//!
+//! Serves the endpoints the node executor uses, with the request shape of the real node:
+//! `GET /status`, `GET /ethAddr`, `POST /reward`, and `POST /withdrawFees` with a single
+//! `amount` form field (the node always withdraws to its own account). Like the real node it
+//! has no `/transferBond`.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+};
+
+#[derive(Clone, Debug)]
+pub struct Stub {
+    pub eth_addr: String,
+    /// `path=code:message`, e.g. `/reward=500:execution reverted`.
+    pub fail: Option<String>,
+}
+
+impl Default for Stub {
+    fn default() -> Self {
+        Self {
+            eth_addr: "0x193e8407c04dd802849fc3f24156b8eed6635819".into(),
+            fail: None,
+        }
+    }
+}
+
+/// Answers connections on `listener` until it fails.
+pub fn serve(listener: TcpListener, stub: Stub) -> io::Result<()> {
+    let stub = Arc::new(stub);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let stub = stub.clone();
+        thread::spawn(move || handle(stream, &stub));
+    }
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, stub: &Stub) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body);
+
+    let (status, body) = respond(stub, &method, &path, &body);
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn respond(stub: &Stub, method: &str, path: &str, body: &str) -> (String, String) {
+    if let Some(fail) = &stub.fail
+        && let Some((fail_path, rest)) = fail.split_once('=')
+        && fail_path == path
+    {
+        let (code, msg) = rest.split_once(':').unwrap_or((rest, "stub failure"));
+        return (format!("{code} Error"), msg.to_owned());
+    }
+
+    match (method, path) {
+        ("GET", "/status") => (
+            "200 OK".into(),
+            r#"{"Version":"stub","Manifests":{},"OrchestratorPool":[]}"#.into(),
+        ),
+        ("GET", "/ethAddr") => ("200 OK".into(), stub.eth_addr.clone()),
+        ("POST", "/reward") => ("200 OK".into(), String::new()),
+        ("POST", "/withdrawFees") => {
+            let form: Vec<(&str, &str)> = body
+                .split('&')
+                .filter(|p| !p.is_empty())
+                .map(|p| p.split_once('=').unwrap_or((p, "")))
+                .collect();
+            // The real node ignores anything but `amount`; a receiver sent here would be lost.
+            if let Some((name, _)) = form.iter().find(|(name, _)| *name != "amount") {
+                return (
+                    "400 Bad Request".into(),
+                    format!("unexpected form param: {name}"),
+                );
+            }
+            match form.iter().find(|(name, _)| *name == "amount") {
+                None => (
+                    "400 Bad Request".into(),
+                    "missing form param: amount".into(),
+                ),
+                Some((_, v)) if v.parse::<u128>().is_err() => {
+                    ("400 Bad Request".into(), format!("invalid amount: {v}"))
+                }
+                Some(_) => ("200 OK".into(), String::new()),
+            }
+        }
+        _ => ("404 Not Found".into(), "404 page not found".into()),
+    }
+}
@@ -0,0 +1,525 @@
+//! Webhook notifications for txs and failures.
+//!
+//! Each target in `NOTIFY_WEBHOOK_URLS` gets a payload in its own format: a generic JSON
+//! object, a Slack-compatible `{"text": ...}` message (Slack, Mattermost, Rocket.Chat) or a
+//! Discord embed. Which events are sent is set by `NOTIFY_EVENTS`, and `NOTIFY_MAX_PER_HOUR`
+//! caps how many go out so a flapping RPC can't flood a channel. Fatal errors bypass the cap.
+//!
+//! Delivery runs on its own task and never holds up the loop: a notification that can't be
+//! delivered is logged and dropped.
+
+use std::{
+    collections::VecDeque,
+    env, fmt, fs,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde_json::{Value, json};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::{AppError, rpc::redact_url};
+
+const QUEUE_LEN: usize = 64;
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+const RATE_WINDOW: Duration = Duration::from_secs(3600);
+/// Longest wait for queued notifications when the process exits.
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotifyEvent {
+    TxSent,
+    TxConfirmed,
+    TxReverted,
+    /// An action or the chain reads failed `NOTIFY_FAILURE_THRESHOLD` times in a row, or an
+    /// action gave up for the round before that.
+    RepeatedFailure,
+    /// The process stopped on a config or startup error.
+    FatalError,
+    /// A round the orchestrator was active in closed without a reward() call.
+    MissedReward,
+    /// The signer balance fell below the warning or critical runway.
+    LowBalance,
+    /// Too few quorum providers confirmed the locked-round values; sent once per round.
+    QuorumFailed,
+}
+
+impl NotifyEvent {
+    const ALL: [NotifyEvent; 8] = [
+        NotifyEvent::TxSent,
+        NotifyEvent::TxConfirmed,
+        NotifyEvent::TxReverted,
+        NotifyEvent::RepeatedFailure,
+        NotifyEvent::FatalError,
+        NotifyEvent::MissedReward,
+        NotifyEvent::LowBalance,
+        NotifyEvent::QuorumFailed,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            NotifyEvent::TxSent => "tx_sent",
+            NotifyEvent::TxConfirmed => "tx_confirmed",
+            NotifyEvent::TxReverted => "tx_reverted",
+            NotifyEvent::RepeatedFailure => "repeated_failure",
+            NotifyEvent::FatalError => "fatal_error",
+            NotifyEvent::MissedReward => "missed_reward",
+            NotifyEvent::LowBalance => "low_balance",
+            NotifyEvent::QuorumFailed => "quorum_failed",
+        }
+    }
+
+    fn severity(self) -> Severity {
+        match self {
+            NotifyEvent::TxSent | NotifyEvent::TxConfirmed => Severity::Info,
+            NotifyEvent::TxReverted | NotifyEvent::RepeatedFailure | NotifyEvent::LowBalance => {
+                Severity::Warning
+            }
+            NotifyEvent::FatalError | NotifyEvent::MissedReward | NotifyEvent::QuorumFailed => {
+                Severity::Critical
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    /// Discord embed color.
+    fn color(self) -> u32 {
+        match self {
+            Severity::Info => 0x2eb67d,
+            Severity::Warning => 0xecb22e,
+            Severity::Critical => 0xe01e5a,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookFormat {
+    Generic,
+    Slack,
+    Discord,
+}
+
+impl WebhookFormat {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "generic" | "json" => Some(WebhookFormat::Generic),
+            "slack" => Some(WebhookFormat::Slack),
+            "discord" => Some(WebhookFormat::Discord),
+            _ => None,
+        }
+    }
+
+    /// Slack and Discord webhook URLs are recognized by host; anything else is generic.
+    fn detect(url: &Url) -> Self {
+        match url.host_str() {
+            Some("hooks.slack.com") => WebhookFormat::Slack,
+            Some("discord.com" | "discordapp.com") if url.path().starts_with("/api/webhooks") => {
+                WebhookFormat::Discord
+            }
+            _ => WebhookFormat::Generic,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhookTarget {
+    pub url: Url,
+    pub format: WebhookFormat,
+}
+
+#[derive(Clone, Debug)]
+pub struct NotifyConfig {
+    pub targets: Vec<WebhookTarget>,
+    pub events: Vec<NotifyEvent>,
+    pub max_per_hour: u32,
+    pub failure_threshold: u32,
+    /// Names this instance in every message.
+    pub label: String,
+}
+
+impl NotifyConfig {
+    /// Reads `NOTIFY_WEBHOOK_URLS` (or `NOTIFY_WEBHOOK_URLS_FILE`), `NOTIFY_EVENTS`,
+    /// `NOTIFY_MAX_PER_HOUR`, `NOTIFY_FAILURE_THRESHOLD` and `NOTIFY_LABEL`. `None` when no
+    /// webhook is configured.
+    pub fn from_env() -> Result<Option<Self>, AppError> {
+        let (key, raw) = match (
+            env::var("NOTIFY_WEBHOOK_URLS"),
+            env::var("NOTIFY_WEBHOOK_URLS_FILE"),
+        ) {
+            (Ok(raw), _) if !raw.trim().is_empty() => ("NOTIFY_WEBHOOK_URLS", raw),
+            (_, Ok(path)) if !path.trim().is_empty() => (
+                "NOTIFY_WEBHOOK_URLS_FILE",
+                fs::read_to_string(path.trim()).map_err(|e| {
+                    AppError::BadEnv("NOTIFY_WEBHOOK_URLS_FILE", format!("{path}: {e}"))
+                })?,
+            ),
+            _ => return Ok(None),
+        };
+        let targets = parse_targets(&raw).map_err(|e| AppError::BadEnv(key, e))?;
+        if targets.is_empty() {
+            return Ok(None);
+        }
+
+        let events = match env::var("NOTIFY_EVENTS") {
+            Ok(raw) if !raw.trim().is_empty() => parse_events(&raw)?,
+            _ => NotifyEvent::ALL.to_vec(),
+        };
+        let max_per_hour = parse_u32("NOTIFY_MAX_PER_HOUR", 20)?;
+        let failure_threshold = parse_u32("NOTIFY_FAILURE_THRESHOLD", 3)?;
+        let label = env::var("NOTIFY_LABEL")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| "funds_transfer".into());
+
+        Ok(Some(Self {
+            targets,
+            events,
+            max_per_hour,
+            failure_threshold,
+            label,
+        }))
+    }
+}
+
+/// `url[;format=generic|slack|discord]`, separated by commas or newlines.
+fn parse_targets(raw: &str) -> Result<Vec<WebhookTarget>, String> {
+    raw.split([',', '\n'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|entry| {
+            let mut parts = entry.split(';');
+            let url = parts.next().unwrap_or_default().trim();
+            let url = Url::parse(url).map_err(|e| format!("invalid webhook url: {e}"))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(format!("{}: expected an http(s) url", redact_url(&url)));
+            }
+            let mut format = WebhookFormat::detect(&url);
+            for opt in parts {
+                match opt.split_once('=') {
+                    Some(("format", v)) => {
+                        format = WebhookFormat::parse(v).ok_or_else(|| {
+                            format!(
+                                "unknown format {v:?}, expected one of: generic, slack, discord"
+                            )
+                        })?;
+                    }
+                    _ => return Err(format!("unknown webhook option {opt:?}")),
+                }
+            }
+            Ok(WebhookTarget { url, format })
+        })
+        .collect()
+}
+
+fn parse_events(raw: &str) -> Result<Vec<NotifyEvent>, AppError> {
+    let mut events = Vec::new();
+    for name in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        if name == "all" {
+            return Ok(NotifyEvent::ALL.to_vec());
+        }
+        let event = NotifyEvent::ALL
+            .into_iter()
+            .find(|e| e.name() == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = NotifyEvent::ALL.iter().map(|e| e.name()).collect();
+                AppError::BadEnv(
+                    "NOTIFY_EVENTS",
+                    format!(
+                        "unknown event {name:?}, expected: all, {}",
+                        names.join(", ")
+                    ),
+                )
+            })?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+fn parse_u32(key: &'static str, default: u32) -> Result<u32, AppError> {
+    match env::var(key) {
+        Ok(raw) => match raw.trim().parse::<u32>() {
+            Ok(0) => Err(AppError::BadEnv(key, "must be at least 1".into())),
+            Ok(v) => Ok(v),
+            Err(e) => Err(AppError::BadEnv(key, format!("{e}"))),
+        },
+        Err(_) => Ok(default),
+    }
+}
+
+/// One message, rendered per target format.
+#[derive(Clone, Debug)]
+pub struct Notification {
+    event: NotifyEvent,
+    severity: Severity,
+    title: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Notification {
+    pub fn new(event: NotifyEvent, title: impl Into<String>) -> Self {
+        Self {
+            event,
+            severity: event.severity(),
+            title: title.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn field(mut self, name: &'static str, value: impl fmt::Display) -> Self {
+        self.fields.push((name, value.to_string()));
+        self
+    }
+}
+
+enum Msg {
+    Send(Notification, u32),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Cheap to clone; a default `Notifier` is disabled and drops everything.
+#[derive(Clone, Default)]
+pub struct Notifier {
+    inner: Option<Arc<Inner>>,
+}
+
+impl fmt::Debug for Notifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notifier")
+            .field("enabled", &self.inner.is_some())
+            .finish()
+    }
+}
+
+struct Inner {
+    tx: mpsc::Sender<Msg>,
+    events: Vec<NotifyEvent>,
+    failure_threshold: u32,
+    max_per_hour: u32,
+    limiter: Mutex<Limiter>,
+}
+
+#[derive(Default)]
+struct Limiter {
+    sent: VecDeque<Instant>,
+    suppressed: u32,
+}
+
+impl Notifier {
+    /// Starts the delivery task; must be called inside the runtime.
+    pub fn spawn(cfg: NotifyConfig) -> Result<Self, AppError> {
+        let http = reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .build()
+            .map_err(|e| AppError::BadEnv("NOTIFY_WEBHOOK_URLS", e.to_string()))?;
+        let (tx, mut rx) = mpsc::channel::<Msg>(QUEUE_LEN);
+
+        for t in &cfg.targets {
+            info!(
+                "webhook notifications: url={} format={:?}",
+                redact_url(&t.url),
+                t.format
+            );
+        }
+        let names: Vec<_> = cfg.events.iter().map(|e| e.name()).collect();
+        info!(
+            "notify events: {} max_per_hour={} failure_threshold={}",
+            names.join(","),
+            cfg.max_per_hour,
+            cfg.failure_threshold
+        );
+
+        let targets = cfg.targets.clone();
+        let label = cfg.label.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                match msg {
+                    Msg::Send(n, suppressed) => {
+                        for t in &targets {
+                            let body = render(t.format, &label, &n, suppressed);
+                            if let Err(e) = post(&http, &t.url, &body).await {
+                                warn!(
+                                    "webhook notification failed: url={} event={} err={}",
+                                    redact_url(&t.url),
+                                    n.event.name(),
+                                    e
+                                );
+                            }
+                        }
+                    }
+                    Msg::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            inner: Some(Arc::new(Inner {
+                tx,
+                events: cfg.events,
+                failure_threshold: cfg.failure_threshold,
+                max_per_hour: cfg.max_per_hour,
+                limiter: Mutex::default(),
+            })),
+        })
+    }
+
+    /// Queues `n` if its event is enabled and the hourly cap allows it.
+    pub fn notify(&self, n: Notification) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        if !inner.events.contains(&n.event) {
+            return;
+        }
+
+        let suppressed = {
+            let mut limiter = inner.limiter.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            while limiter
+                .sent
+                .front()
+                .is_some_and(|t| now.duration_since(*t) >= RATE_WINDOW)
+            {
+                limiter.sent.pop_front();
+            }
+            if !matches!(n.event, NotifyEvent::FatalError | NotifyEvent::MissedReward)
+                && limiter.sent.len() >= inner.max_per_hour as usize
+            {
+                if limiter.suppressed == 0 {
+                    warn!(
+                        "webhook notifications rate limited: max_per_hour={}",
+                        inner.max_per_hour
+                    );
+                }
+                limiter.suppressed += 1;
+                debug!("webhook notification suppressed: event={}", n.event.name());
+                return;
+            }
+            limiter.sent.push_back(now);
+            std::mem::take(&mut limiter.suppressed)
+        };
+
+        if inner.tx.try_send(Msg::Send(n, suppressed)).is_err() {
+            warn!("webhook notification dropped: delivery queue full");
+        }
+    }
+
+    /// Whether `attempts` failures in a row (and possibly giving up) call for a
+    /// `repeated_failure` notification. Fires once per streak.
+    pub fn failure_alert_due(&self, attempts: u32, gave_up: bool) -> bool {
+        self.inner.as_ref().is_some_and(|inner| {
+            attempts == inner.failure_threshold || (gave_up && attempts < inner.failure_threshold)
+        })
+    }
+
+    /// Waits for queued notifications to be delivered, up to `timeout`.
+    pub async fn flush(&self, timeout: Duration) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let (done_tx, done_rx) = oneshot::channel();
+        if inner.tx.send(Msg::Flush(done_tx)).await.is_ok() {
+            let _ = tokio::time::timeout(timeout, done_rx).await;
+        }
+    }
+}
+
+async fn post(http: &reqwest::Client, url: &Url, body: &Value) -> Result<(), String> {
+    let resp = http
+        .post(url.clone())
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| e.without_url().to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("webhook answered {}", resp.status()));
+    }
+    Ok(())
+}
+
+fn render(format: WebhookFormat, label: &str, n: &Notification, suppressed: u32) -> Value {
+    let note = (suppressed > 0)
+        .then(|| format!("{suppressed} earlier notification(s) were dropped by the rate limit"));
+    match format {
+        WebhookFormat::Generic => {
+            let fields: serde_json::Map<String, Value> = n
+                .fields
+                .iter()
+                .map(|(k, v)| ((*k).to_owned(), v.clone().into()))
+                .collect();
+            json!({
+                "source": label,
+                "event": n.event.name(),
+                "severity": n.severity.to_string(),
+                "title": n.title,
+                "fields": fields,
+                "suppressed": suppressed,
+                "timestamp": unix_secs(),
+            })
+        }
+        WebhookFormat::Slack => {
+            let icon = match n.severity {
+                Severity::Info => ":white_check_mark:",
+                Severity::Warning => ":warning:",
+                Severity::Critical => ":rotating_light:",
+            };
+            let mut text = format!("{icon} *[{label}] {}*", n.title);
+            for (k, v) in &n.fields {
+                text.push_str(&format!("\n• {k}: `{v}`"));
+            }
+            if let Some(note) = note {
+                text.push_str(&format!("\n_{note}_"));
+            }
+            json!({ "text": text })
+        }
+        WebhookFormat::Discord => {
+            let fields: Vec<Value> = n
+                .fields
+                .iter()
+                .map(|(k, v)| json!({ "name": k, "value": v, "inline": v.len() <= 24 }))
+                .collect();
+            let mut embed = json!({
+                "title": format!("[{label}] {}", n.title),
+                "color": n.severity.color(),
+                "fields": fields,
+            });
+            if let Some(note) = note {
+                embed["footer"] = json!({ "text": note });
+            }
+            json!({ "username": label, "embeds": [embed] })
+        }
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
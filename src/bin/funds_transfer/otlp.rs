@@ -0,0 +1,365 @@
+//! OpenTelemetry trace export over OTLP/HTTP (JSON encoding).
+//!
+//! When `OTEL_EXPORTER_OTLP_ENDPOINT` is set, spans from this crate are collected by a tracing
+//! layer and posted in batches to `{endpoint}/v1/traces`: one `loop` span per iteration with
+//! `rpc` spans for each endpoint request and `tx` spans for each send-and-confirm (or node
+//! request) underneath. Span fields become attributes; a span that records an `error` field is
+//! marked as failed. Only the crate's own spans are exported, and log output is unaffected.
+//!
+//! Export is best effort: spans are dropped when the collector is unreachable or the queue is
+//! full, and the loop never waits on it.
+
+use std::{
+    env, fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde_json::{Value, json};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{
+    Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+use url::Url;
+
+use crate::AppError;
+
+const QUEUE_LEN: usize = 2048;
+const BATCH_LEN: usize = 512;
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest wait for the last batch when the process exits.
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// OTel span kinds used here.
+const KIND_INTERNAL: u8 = 1;
+const KIND_CLIENT: u8 = 3;
+
+#[derive(Clone, Debug)]
+pub struct OtlpConfig {
+    pub traces_url: Url,
+    pub service_name: String,
+    pub headers: HeaderMap,
+}
+
+impl OtlpConfig {
+    /// Reads `OTEL_EXPORTER_OTLP_ENDPOINT`, `OTEL_EXPORTER_OTLP_HEADERS` and `OTEL_SERVICE_NAME`.
+    pub fn from_env() -> Result<Option<Self>, AppError> {
+        let raw = match env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            Ok(raw) if !raw.trim().is_empty() => raw,
+            _ => return Ok(None),
+        };
+        let bad = |e: String| AppError::BadEnv("OTEL_EXPORTER_OTLP_ENDPOINT", e);
+        let mut base = Url::parse(raw.trim()).map_err(|e| bad(e.to_string()))?;
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        let traces_url = base.join("v1/traces").map_err(|e| bad(e.to_string()))?;
+
+        let mut headers = HeaderMap::new();
+        if let Ok(raw) = env::var("OTEL_EXPORTER_OTLP_HEADERS") {
+            for pair in raw.split(',').filter(|p| !p.trim().is_empty()) {
+                let bad = || {
+                    AppError::BadEnv(
+                        "OTEL_EXPORTER_OTLP_HEADERS",
+                        "expected key=value pairs separated by commas".into(),
+                    )
+                };
+                let (k, v) = pair.split_once('=').ok_or_else(bad)?;
+                let name = HeaderName::from_bytes(k.trim().as_bytes()).map_err(|_| bad())?;
+                let mut value = HeaderValue::from_str(v.trim()).map_err(|_| bad())?;
+                value.set_sensitive(true);
+                headers.insert(name, value);
+            }
+        }
+
+        let service_name = env::var("OTEL_SERVICE_NAME")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| "funds_transfer".into());
+
+        Ok(Some(Self {
+            traces_url,
+            service_name,
+            headers,
+        }))
+    }
+}
+
+/// Finished span, ready to encode.
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_id: Option<[u8; 8]>,
+    name: &'static str,
+    kind: u8,
+    start: SystemTime,
+    end: SystemTime,
+    attrs: Vec<(&'static str, Value)>,
+    error: Option<String>,
+}
+
+/// Per-span state kept in the registry until the span closes.
+struct SpanState {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_id: Option<[u8; 8]>,
+    start: SystemTime,
+    attrs: Vec<(&'static str, Value)>,
+    error: Option<String>,
+}
+
+pub struct OtlpLayer {
+    tx: mpsc::Sender<SpanData>,
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span.parent().and_then(|p| {
+            p.extensions()
+                .get::<SpanState>()
+                .map(|s| (s.trace_id, s.span_id))
+        });
+        let mut rng = rand::thread_rng();
+        let mut state = SpanState {
+            trace_id: parent.map(|p| p.0).unwrap_or_else(|| rng.r#gen()),
+            span_id: rng.r#gen(),
+            parent_id: parent.map(|p| p.1),
+            start: SystemTime::now(),
+            attrs: Vec::new(),
+            error: None,
+        };
+        attrs.record(&mut AttrVisitor(&mut state));
+        span.extensions_mut().insert(state);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(state) = span.extensions_mut().get_mut::<SpanState>()
+        {
+            values.record(&mut AttrVisitor(state));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(state) = span.extensions_mut().remove::<SpanState>() else {
+            return;
+        };
+        let name = span.name();
+        // Dropped when the exporter falls behind; tracing must never block the loop.
+        let _ = self.tx.try_send(SpanData {
+            trace_id: state.trace_id,
+            span_id: state.span_id,
+            parent_id: state.parent_id,
+            name,
+            kind: if name == "rpc" {
+                KIND_CLIENT
+            } else {
+                KIND_INTERNAL
+            },
+            start: state.start,
+            end: SystemTime::now(),
+            attrs: state.attrs,
+            error: state.error,
+        });
+    }
+}
+
+struct AttrVisitor<'a>(&'a mut SpanState);
+
+impl AttrVisitor<'_> {
+    fn set(&mut self, field: &Field, value: Value) {
+        if field.name() == "error" {
+            self.0.error = Some(match value {
+                Value::String(s) => s,
+                v => v.to_string(),
+            });
+            return;
+        }
+        let attrs = &mut self.0.attrs;
+        match attrs.iter_mut().find(|(k, _)| *k == field.name()) {
+            Some(slot) => slot.1 = value,
+            None => attrs.push((field.name(), value)),
+        }
+    }
+}
+
+impl Visit for AttrVisitor<'_> {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field, json!({ "boolValue": value }));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        // int64 is a string in OTLP JSON.
+        self.set(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field, json!({ "doubleValue": value }));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, json!({ "stringValue": value }));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.set(field, json!({ "stringValue": format!("{value:?}") }));
+    }
+}
+
+/// Handle to the background exporter.
+pub struct OtlpExporter {
+    flush: mpsc::Sender<oneshot::Sender<()>>,
+}
+
+impl OtlpExporter {
+    /// Sends whatever is queued and waits for it, up to `timeout`.
+    pub async fn flush(&self, timeout: Duration) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.flush.send(done_tx).await.is_ok() {
+            let _ = tokio::time::timeout(timeout, done_rx).await;
+        }
+    }
+}
+
+/// Creates the layer and starts the exporter task; must be called inside the runtime.
+pub fn spawn(cfg: OtlpConfig) -> Result<(OtlpLayer, OtlpExporter), AppError> {
+    let http = reqwest::Client::builder()
+        .timeout(EXPORT_TIMEOUT)
+        .default_headers(cfg.headers.clone())
+        .build()
+        .map_err(|e| AppError::BadEnv("OTEL_EXPORTER_OTLP_ENDPOINT", e.to_string()))?;
+    let (tx, mut rx) = mpsc::channel::<SpanData>(QUEUE_LEN);
+    let (flush_tx, mut flush_rx) = mpsc::channel::<oneshot::Sender<()>>(1);
+
+    tokio::spawn(async move {
+        let mut batch = Vec::with_capacity(BATCH_LEN);
+        let mut tick = tokio::time::interval(EXPORT_INTERVAL);
+        let mut failing = false;
+        loop {
+            let done = tokio::select! {
+                span = rx.recv() => match span {
+                    Some(span) => {
+                        batch.push(span);
+                        if batch.len() < BATCH_LEN {
+                            continue;
+                        }
+                        None
+                    }
+                    None => return,
+                },
+                _ = tick.tick() => None,
+                done = flush_rx.recv() => done,
+            };
+
+            // Take everything already queued, e.g. the spans of a loop that just ended.
+            while let Ok(span) = rx.try_recv() {
+                batch.push(span);
+            }
+            if !batch.is_empty() {
+                let body = encode(&cfg.service_name, &batch);
+                batch.clear();
+                // Logged without trace context: these events are not exported themselves.
+                match post(&http, &cfg.traces_url, &body).await {
+                    Ok(()) if failing => {
+                        failing = false;
+                        tracing::info!("otlp export recovered");
+                    }
+                    Ok(()) => {}
+                    Err(e) if !failing => {
+                        failing = true;
+                        tracing::warn!("otlp export failed, dropping spans until it recovers: {e}");
+                    }
+                    Err(e) => tracing::debug!("otlp export failed: {e}"),
+                }
+            }
+            if let Some(done) = done {
+                let _ = done.send(());
+            }
+        }
+    });
+
+    Ok((OtlpLayer { tx }, OtlpExporter { flush: flush_tx }))
+}
+
+async fn post(http: &reqwest::Client, url: &Url, body: &Value) -> Result<(), String> {
+    let resp = http
+        .post(url.clone())
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| e.without_url().to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("collector answered {}", resp.status()));
+    }
+    Ok(())
+}
+
+fn encode(service_name: &str, spans: &[SpanData]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|s| {
+            let mut span = json!({
+                "traceId": hex(&s.trace_id),
+                "spanId": hex(&s.span_id),
+                "name": s.name,
+                "kind": s.kind,
+                "startTimeUnixNano": unix_nanos(s.start),
+                "endTimeUnixNano": unix_nanos(s.end),
+                "attributes": s.attrs.iter().map(|(k, v)| json!({ "key": k, "value": v })).collect::<Vec<_>>(),
+                "status": match &s.error {
+                    Some(msg) => json!({ "code": 2, "message": msg }),
+                    None => json!({ "code": 0 }),
+                },
+            });
+            if let Some(parent) = &s.parent_id {
+                span["parentSpanId"] = hex(parent).into();
+            }
+            span
+        })
+        .collect();
+
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": service_name } },
+                    { "key": "service.version", "value": { "stringValue": env!("CARGO_PKG_VERSION") } },
+                ],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "funds_transfer", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// fixed64 is a string in OTLP JSON.
+fn unix_nanos(t: SystemTime) -> String {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+        .to_string()
+}
@@ -0,0 +1,475 @@
+//! Payouts split across several receivers.
+//!
+//! A split is a list of receivers with a share in basis points; one of them takes the rounding
+//! dust. Paying out a split takes one tx per receiver, so the legs of a payout are planned up
+//! front and kept in `STATE_DIR/payouts.json` until every leg is done. A failed leg is retried
+//! on its own, and the legs already paid are skipped. A leg that was broadcast but not
+//! confirmed keeps its tx hash; its receipt is checked before the leg is sent again, so nobody
+//! is paid twice.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ethers::{
+    providers::Middleware,
+    types::{Address, TxHash, U64, U256},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::AppError;
+
+const PAYOUTS_FILE: &str = "payouts.json";
+const TOTAL_BPS: u32 = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Share {
+    pub to: Address,
+    pub bps: u32,
+    /// Takes what is left after rounding the other shares down.
+    pub dust: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Split {
+    shares: Vec<Share>,
+}
+
+impl Split {
+    /// All of it to `to`.
+    pub fn single(to: Address) -> Self {
+        Self {
+            shares: vec![Share {
+                to,
+                bps: TOTAL_BPS,
+                dust: true,
+            }],
+        }
+    }
+
+    /// Comma-separated `address;bps=N[;dust]`. Shares must add up to 10000; the dust receiver
+    /// defaults to the first entry.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let mut shares: Vec<Share> = Vec::new();
+        for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let mut parts = entry.split(';').map(str::trim);
+            let addr = parts.next().unwrap_or_default();
+            let to: Address = addr
+                .parse()
+                .map_err(|e| format!("bad address {addr:?}: {e}"))?;
+            let mut bps = None;
+            let mut dust = false;
+            for opt in parts {
+                match opt.split_once('=') {
+                    Some(("bps", v)) => {
+                        bps = Some(
+                            v.parse::<u32>()
+                                .map_err(|e| format!("bad bps for {addr}: {e}"))?,
+                        )
+                    }
+                    None if opt == "dust" => dust = true,
+                    _ => return Err(format!("unknown option {opt:?} for {addr}")),
+                }
+            }
+            let bps = bps.ok_or_else(|| format!("missing bps for {addr}"))?;
+            if bps == 0 {
+                return Err(format!("bps for {addr} must be above 0"));
+            }
+            if shares.iter().any(|s| s.to == to) {
+                return Err(format!("{addr} is listed twice"));
+            }
+            shares.push(Share { to, bps, dust });
+        }
+        if shares.is_empty() {
+            return Err("no receivers".into());
+        }
+        let total: u32 = shares.iter().map(|s| s.bps).sum();
+        if total != TOTAL_BPS {
+            return Err(format!("bps add up to {total}, expected {TOTAL_BPS}"));
+        }
+        match shares.iter().filter(|s| s.dust).count() {
+            0 => shares[0].dust = true,
+            1 => {}
+            _ => return Err("only one receiver can take the dust".into()),
+        }
+        Ok(Self { shares })
+    }
+
+    /// `total` divided by share, rounded down, with the remainder added to the dust receiver.
+    /// Receivers whose amount rounds to zero are left out.
+    pub fn amounts(&self, total: U256) -> Vec<(Address, U256)> {
+        let mut amounts: Vec<(Address, U256)> = self
+            .shares
+            .iter()
+            .map(|s| (s.to, total * U256::from(s.bps) / U256::from(TOTAL_BPS)))
+            .collect();
+        let paid = amounts.iter().fold(U256::zero(), |sum, (_, a)| sum + a);
+        if let Some(i) = self.shares.iter().position(|s| s.dust) {
+            amounts[i].1 += total - paid;
+        }
+        amounts.retain(|(_, a)| !a.is_zero());
+        amounts
+    }
+
+    pub fn receivers(&self) -> impl Iterator<Item = Address> + '_ {
+        self.shares.iter().map(|s| s.to)
+    }
+
+    pub fn describe(&self) -> String {
+        self.shares
+            .iter()
+            .map(|s| {
+                format!(
+                    "{:?}={}bps{}",
+                    s.to,
+                    s.bps,
+                    if s.dust { "(dust)" } else { "" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Leg {
+    pub to: Address,
+    pub amount: U256,
+    pub done: bool,
+    /// Broadcast but not confirmed.
+    pub tx_hash: Option<TxHash>,
+    /// Gas for the signer rather than a receiver's share.
+    #[serde(default)]
+    pub topup: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Plan {
+    pub round: U256,
+    pub legs: Vec<Leg>,
+}
+
+impl Plan {
+    pub fn has_unpaid_topup(&self) -> bool {
+        self.legs.iter().any(|l| l.topup && !l.done)
+    }
+
+    pub fn has_receiver_legs(&self) -> bool {
+        self.legs.iter().any(|l| !l.topup)
+    }
+
+    /// Amount of the legs not paid yet.
+    pub fn remaining(&self) -> U256 {
+        self.legs
+            .iter()
+            .filter(|l| !l.done)
+            .fold(U256::zero(), |sum, l| sum + l.amount)
+    }
+}
+
+/// Unfinished payouts by action, mirrored to disk when a state dir is configured.
+#[derive(Debug)]
+pub struct PayoutStore {
+    path: Option<PathBuf>,
+    state: State,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    plans: BTreeMap<String, Plan>,
+    /// Round each action last planned a payout in, kept after the payout is done.
+    #[serde(default)]
+    planned: BTreeMap<String, U256>,
+}
+
+impl PayoutStore {
+    pub fn open(state_dir: Option<&Path>) -> Self {
+        let path = state_dir.map(|d| d.join(PAYOUTS_FILE));
+        let state: State = match path.as_deref().map(fs::read_to_string) {
+            Some(Ok(raw)) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                warn!("ignoring unreadable payouts file: err={e}");
+                State::default()
+            }),
+            _ => State::default(),
+        };
+        for (action, plan) in &state.plans {
+            info!(
+                "resuming {action} payout from round {}: legs_left={} remaining_wei={}",
+                plan.round,
+                plan.legs.iter().filter(|l| !l.done).count(),
+                plan.remaining()
+            );
+        }
+        Self { path, state }
+    }
+
+    pub fn outstanding(&self, action: &str) -> Option<&Plan> {
+        self.state.plans.get(action)
+    }
+
+    /// Whether a payout for `action` was planned in `round`, finished or not.
+    pub fn planned_in(&self, action: &str, round: U256) -> bool {
+        self.state.planned.get(action) == Some(&round)
+    }
+
+    /// Adds legs to the action's payout, starting one if there is none. Top-up legs go first.
+    pub fn add(&mut self, action: &str, round: U256, amounts: Vec<(Address, U256)>, topup: bool) {
+        let legs = amounts.into_iter().map(|(to, amount)| Leg {
+            to,
+            amount,
+            done: false,
+            tx_hash: None,
+            topup,
+        });
+        self.state.planned.insert(action.to_owned(), round);
+        let plan = self.state.plans.entry(action.to_owned()).or_insert(Plan {
+            round,
+            legs: Vec::new(),
+        });
+        if topup {
+            plan.legs.splice(0..0, legs);
+        } else {
+            plan.legs.extend(legs);
+        }
+        self.persist();
+    }
+
+    /// Drops the legs not paid yet.
+    pub fn abandon(&mut self, action: &str) {
+        if self.state.plans.remove(action).is_some() {
+            self.persist();
+        }
+    }
+
+    /// Index and copy of the next leg to pay.
+    pub fn next_leg(&self, action: &str) -> Option<(usize, Leg)> {
+        let plan = self.state.plans.get(action)?;
+        plan.legs
+            .iter()
+            .enumerate()
+            .find(|(_, l)| !l.done)
+            .map(|(i, l)| (i, l.clone()))
+    }
+
+    pub fn leg_done(&mut self, action: &str, leg: usize) {
+        if let Some(plan) = self.state.plans.get_mut(action) {
+            plan.legs[leg].done = true;
+            plan.legs[leg].tx_hash = None;
+            if plan.legs.iter().all(|l| l.done) {
+                self.state.plans.remove(action);
+            }
+            self.persist();
+        }
+    }
+
+    /// The leg's tx went out but its outcome is unknown.
+    pub fn leg_sent(&mut self, action: &str, leg: usize, tx_hash: TxHash) {
+        if let Some(plan) = self.state.plans.get_mut(action) {
+            plan.legs[leg].tx_hash = Some(tx_hash);
+            self.persist();
+        }
+    }
+
+    /// Looks up the legs broadcast by an earlier attempt: mined ones are done, reverted or
+    /// dropped ones may be sent again. Returns false while one is still pending.
+    pub async fn settle<M: Middleware + 'static>(
+        &mut self,
+        action: &str,
+        client: &M,
+    ) -> Result<bool, AppError> {
+        let Some(plan) = self.state.plans.get(action) else {
+            return Ok(true);
+        };
+        let sent: Vec<(usize, TxHash)> = plan
+            .legs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, l)| l.tx_hash.filter(|_| !l.done).map(|h| (i, h)))
+            .collect();
+
+        for (i, tx_hash) in sent {
+            let receipt = client
+                .get_transaction_receipt(tx_hash)
+                .await
+                .map_err(|e| AppError::middleware("eth_getTransactionReceipt", &e))?;
+            match receipt {
+                Some(r) if r.status != Some(U64::zero()) => {
+                    info!("{action} payout leg {i} settled: tx_hash={tx_hash:?} (mined)");
+                    self.leg_done(action, i);
+                }
+                Some(_) => {
+                    info!("{action} payout leg {i} reverted: tx_hash={tx_hash:?}; sending again");
+                    self.clear_sent(action, i);
+                }
+                None => {
+                    let tx = client
+                        .get_transaction(tx_hash)
+                        .await
+                        .map_err(|e| AppError::middleware("eth_getTransactionByHash", &e))?;
+                    if tx.is_some() {
+                        info!("{action} payout leg {i} still pending: tx_hash={tx_hash:?}");
+                        return Ok(false);
+                    }
+                    warn!("{action} payout leg {i} tx dropped: tx_hash={tx_hash:?}; sending again");
+                    self.clear_sent(action, i);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn clear_sent(&mut self, action: &str, leg: usize) {
+        if let Some(plan) = self.state.plans.get_mut(action) {
+            plan.legs[leg].tx_hash = None;
+            self.persist();
+        }
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let tmp = path.with_extension("json.tmp");
+        let res = serde_json::to_vec_pretty(&self.state)
+            .map_err(|e| e.to_string())
+            .and_then(|data| fs::write(&tmp, data).map_err(|e| e.to_string()))
+            .and_then(|()| fs::rename(&tmp, path).map_err(|e| e.to_string()));
+        if let Err(e) = res {
+            warn!(
+                "failed to write payouts file: path={} err={e}",
+                path.display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retain::RetainPolicy;
+
+    const A: &str = "0x1111111111111111111111111111111111111111";
+    const B: &str = "0x2222222222222222222222222222222222222222";
+    const C: &str = "0x3333333333333333333333333333333333333333";
+
+    fn addr(s: &str) -> Address {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_shares_and_defaults_dust_to_first() {
+        let split = Split::parse(&format!("{A};bps=7000, {B};bps=3000")).unwrap();
+        assert_eq!(
+            split.shares,
+            vec![
+                Share {
+                    to: addr(A),
+                    bps: 7000,
+                    dust: true
+                },
+                Share {
+                    to: addr(B),
+                    bps: 3000,
+                    dust: false
+                },
+            ]
+        );
+
+        let split = Split::parse(&format!("{A};bps=5000,{B};bps=5000;dust")).unwrap();
+        assert!(!split.shares[0].dust);
+        assert!(split.shares[1].dust);
+    }
+
+    #[test]
+    fn rejects_bad_splits() {
+        for raw in [
+            String::new(),
+            format!("{A};bps=6000,{B};bps=3000"),
+            format!("{A};bps=5000,{A};bps=5000"),
+            format!("{A};bps=10000,{B};bps=0"),
+            format!("{A};bps=5000;dust,{B};bps=5000;dust"),
+            A.to_string(),
+            format!("{A};bps=10000;share=1"),
+            "0x12;bps=10000".into(),
+        ] {
+            assert!(Split::parse(&raw).is_err(), "accepted {raw:?}");
+        }
+    }
+
+    #[test]
+    fn amounts_give_the_dust_to_one_receiver() {
+        let split = Split::parse(&format!("{A};bps=3333,{B};bps=3333,{C};bps=3334;dust")).unwrap();
+        let amounts = split.amounts(U256::from(100));
+        assert_eq!(
+            amounts,
+            vec![
+                (addr(A), U256::from(33)),
+                (addr(B), U256::from(33)),
+                (addr(C), U256::from(34)),
+            ]
+        );
+
+        let split = Split::parse(&format!("{A};bps=9999,{B};bps=1")).unwrap();
+        let amounts = split.amounts(U256::from(1001));
+        assert_eq!(amounts[0], (addr(A), U256::from(1001)));
+        assert_eq!(amounts.len(), 1, "zero legs are left out: {amounts:?}");
+        assert!(split.amounts(U256::zero()).is_empty());
+    }
+
+    #[test]
+    fn topup_legs_go_first_and_done_plans_are_removed() {
+        let mut store = PayoutStore::open(None);
+        let round = U256::from(7);
+        store.add("withdrawFees", round, vec![(addr(A), U256::from(5))], false);
+        store.add("withdrawFees", round, vec![(addr(B), U256::from(2))], true);
+
+        let plan = store.outstanding("withdrawFees").unwrap();
+        assert!(plan.has_unpaid_topup());
+        assert!(plan.has_receiver_legs());
+        assert_eq!(plan.remaining(), U256::from(7));
+        let (i, leg) = store.next_leg("withdrawFees").unwrap();
+        assert_eq!((i, leg.to, leg.topup), (0, addr(B), true));
+
+        store.leg_done("withdrawFees", 0);
+        assert_eq!(
+            store.outstanding("withdrawFees").unwrap().remaining(),
+            U256::from(5)
+        );
+        store.leg_done("withdrawFees", 1);
+        assert!(store.outstanding("withdrawFees").is_none());
+    }
+
+    #[test]
+    fn a_round_is_planned_once_even_across_restarts() {
+        let dir = std::env::temp_dir().join(format!("payouts-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let policy = RetainPolicy::PendingShare {
+            bps: 5000,
+            min: U256::zero(),
+        };
+        let round = U256::from(7);
+
+        // First loop of the round: half of pendingStake goes out.
+        let mut store = PayoutStore::open(Some(&dir));
+        let pending = U256::from(100);
+        assert!(!store.planned_in("transferBond", round));
+        let transferable = pending - policy.retain(pending, U256::zero());
+        store.add("transferBond", round, vec![(addr(A), transferable)], false);
+        store.leg_done("transferBond", 0);
+
+        // Second loop, after a restart: half of what is left would be transferable again, but
+        // the round already had its payout.
+        let store = PayoutStore::open(Some(&dir));
+        let pending = pending - transferable;
+        assert!(!(pending - policy.retain(pending, U256::zero())).is_zero());
+        assert!(store.outstanding("transferBond").is_none());
+        assert!(store.planned_in("transferBond", round));
+        assert!(!store.planned_in("transferBond", round + 1));
+        assert!(!store.planned_in("withdrawFees", round));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
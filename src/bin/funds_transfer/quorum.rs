@@ -0,0 +1,261 @@
+//! Quorum confirmation before moving funds.
+//!
+//! `transferBond` and `withdrawFees` act on `pendingStake` / `pendingFees` (and
+//! `transcoderTotalStake` with the `stake_share` retain policy) and the round lock as read
+//! from the primary provider. With `QUORUM_MIN` set, those values are re-read from each
+//! quorum provider at the same block and the action only proceeds when at least `QUORUM_MIN`
+//! of them return identical values. A lagging provider that doesn't have the block yet, or one
+//! serving fabricated state, simply doesn't count towards the quorum.
+//!
+//! A failed check goes through the action's retry backoff like any other failure, and is
+//! logged at ERROR and sent as a `quorum_failed` notification once per round.
+
+use std::sync::{Arc, Mutex};
+
+use ethers::{
+    providers::Provider,
+    types::{Address, U64, U256},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    AppError, BondingManager, RoundsManager,
+    notify::{Notification, Notifier, NotifyEvent},
+    retry::ErrorKind,
+    rpc::{EndpointSpec, FailoverClient, RpcTimeouts},
+};
+
+/// The values a locked-round action depends on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockedValues {
+    pub round: U256,
+    pub locked: bool,
+    pub pending_stake: Option<U256>,
+    pub pending_fees: Option<U256>,
+    pub total_stake: Option<U256>,
+}
+
+struct QuorumMember {
+    label: String,
+    rounds: RoundsManager<Provider<FailoverClient>>,
+    bonding: BondingManager<Provider<FailoverClient>>,
+}
+
+pub struct QuorumGate {
+    members: Vec<QuorumMember>,
+    min_agree: usize,
+    notifier: Notifier,
+    state: Mutex<GateState>,
+}
+
+#[derive(Default)]
+struct GateState {
+    /// Outcome of the latest check, reused while the block and values are the same.
+    last: Option<(U64, LockedValues, Result<(), String>)>,
+    /// Round the latest failure was alerted in.
+    alerted_round: Option<U256>,
+}
+
+impl QuorumGate {
+    /// One independent provider per endpoint; no failover between members, since each one is
+    /// supposed to be a separate witness.
+    pub fn new(
+        specs: &[EndpointSpec],
+        timeouts: RpcTimeouts,
+        min_agree: usize,
+        rounds_manager_addr: Address,
+        bonding_manager_addr: Address,
+        notifier: Notifier,
+    ) -> Result<Self, AppError> {
+        let mut members = Vec::with_capacity(specs.len());
+        for spec in specs {
+            let client = FailoverClient::new(vec![spec.clone()])
+                .map_err(|e| AppError::Chain(ErrorKind::Config, e))?
+                .with_timeouts(timeouts);
+            let label = client
+                .describe()
+                .into_iter()
+                .next()
+                .map(|(label, _)| label)
+                .unwrap_or_default();
+            let provider = Arc::new(Provider::new(client));
+            members.push(QuorumMember {
+                label,
+                rounds: RoundsManager::new(rounds_manager_addr, provider.clone()),
+                bonding: BondingManager::new(bonding_manager_addr, provider),
+            });
+        }
+
+        info!(
+            "quorum reads enabled: min_agree={} providers={}",
+            min_agree,
+            members.len()
+        );
+        Ok(Self {
+            members,
+            min_agree,
+            notifier,
+            state: Mutex::default(),
+        })
+    }
+
+    /// Ok if at least `min_agree` providers return exactly `expected` at `block`. Asking again
+    /// for the same block and values returns the earlier outcome without new reads.
+    pub async fn confirm(
+        &self,
+        orchestrator: Address,
+        block: U64,
+        expected: &LockedValues,
+    ) -> Result<(), AppError> {
+        if let Some((b, v, res)) = &self.lock().last
+            && *b == block
+            && v == expected
+        {
+            return res.clone().map_err(AppError::Quorum);
+        }
+        let res = self.check(orchestrator, block, expected).await;
+        self.lock().last = Some((block, *expected, res.clone()));
+        res.map_err(AppError::Quorum)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, GateState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn check(
+        &self,
+        orchestrator: Address,
+        block: U64,
+        expected: &LockedValues,
+    ) -> Result<(), String> {
+        let mut agree = 0usize;
+        for m in &self.members {
+            match read_member(m, orchestrator, block, expected).await {
+                Ok(observed) if observed == *expected => {
+                    debug!("quorum member agrees: provider={} block={}", m.label, block);
+                    agree += 1;
+                }
+                Ok(observed) => {
+                    warn!(
+                        "quorum member disagrees: provider={} block={} expected={:?} observed={:?}",
+                        m.label, block, expected, observed
+                    );
+                }
+                Err(e) => {
+                    warn!(
+                        "quorum member read failed: provider={} block={} err={}",
+                        m.label, block, e
+                    );
+                }
+            }
+        }
+
+        if agree >= self.min_agree {
+            debug!(
+                "quorum reached: {}/{} providers agree at block {} (need {})",
+                agree,
+                self.members.len(),
+                block,
+                self.min_agree
+            );
+            return Ok(());
+        }
+
+        let msg = format!(
+            "{agree}/{} providers confirmed locked-round values at block {block} (need {})",
+            self.members.len(),
+            self.min_agree
+        );
+        let first_in_round = {
+            let mut state = self.lock();
+            let first = state.alerted_round != Some(expected.round);
+            state.alerted_round = Some(expected.round);
+            first
+        };
+        if first_in_round {
+            error!(
+                event = "quorum_failed",
+                round = %expected.round,
+                "ALERT quorum not reached: {msg}; skipping fund-moving actions. expected={expected:?}"
+            );
+            self.notifier.notify(
+                Notification::new(NotifyEvent::QuorumFailed, "quorum not reached")
+                    .field("round", expected.round)
+                    .field("block", block)
+                    .field("agree", agree)
+                    .field("need", self.min_agree)
+                    .field("providers", self.members.len()),
+            );
+        } else {
+            warn!("quorum not reached: {msg}; skipping fund-moving actions");
+        }
+        Err(msg)
+    }
+}
+
+async fn read_member(
+    m: &QuorumMember,
+    orchestrator: Address,
+    block: U64,
+    expected: &LockedValues,
+) -> Result<LockedValues, AppError> {
+    let round = m
+        .rounds
+        .current_round()
+        .block(block)
+        .call()
+        .await
+        .map_err(|e| AppError::contract("RoundsManager.currentRound()", &e))?;
+
+    let locked = m
+        .rounds
+        .current_round_locked()
+        .block(block)
+        .call()
+        .await
+        .map_err(|e| AppError::contract("RoundsManager.currentRoundLocked()", &e))?;
+
+    let pending_stake = match expected.pending_stake {
+        Some(_) => Some(
+            m.bonding
+                .pending_stake(orchestrator, round)
+                .block(block)
+                .call()
+                .await
+                .map_err(|e| AppError::contract("pendingStake()", &e))?,
+        ),
+        None => None,
+    };
+
+    let pending_fees = match expected.pending_fees {
+        Some(_) => Some(
+            m.bonding
+                .pending_fees(orchestrator, round)
+                .block(block)
+                .call()
+                .await
+                .map_err(|e| AppError::contract("pendingFees()", &e))?,
+        ),
+        None => None,
+    };
+
+    let total_stake = match expected.total_stake {
+        Some(_) => Some(
+            m.bonding
+                .transcoder_total_stake(orchestrator)
+                .block(block)
+                .call()
+                .await
+                .map_err(|e| AppError::contract("transcoderTotalStake()", &e))?,
+        ),
+        None => None,
+    };
+
+    Ok(LockedValues {
+        round,
+        locked,
+        pending_stake,
+        pending_fees,
+        total_stake,
+    })
+}
@@ -0,0 +1,498 @@
+//! Per-loop chain reads.
+//!
+//! Everything the loop decides on is read at a single L2 block: the RoundsManager round state
+//! (and schedule, when something needs it), plus the BondingManager values for the
+//! orchestrator. With Multicall3 available
+//! the reads go out as one `aggregate3` eth_call; otherwise they are issued one by one, still
+//! pinned to the same block so the values stay consistent with each other.
+
+use std::sync::Arc;
+
+use ethers::{
+    abi::Token,
+    contract::{Multicall, MulticallVersion},
+    providers::Middleware,
+    types::{Address, Bytes, U64, U256},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    AppError, BondingManager, RoundState, RoundsManager, retry::ErrorKind, scheduler::RoundSchedule,
+};
+
+/// Which values beyond the round state the enabled features need.
+#[derive(Clone, Copy, Debug)]
+pub struct ReadPlan {
+    /// The RoundsManager schedule (`blockNum`, `currentRoundStartBlock`, `roundLength`,
+    /// `roundLockAmount`), for block scheduling and backup mode.
+    pub schedule: bool,
+    pub transcoder: bool,
+    pub pending_stake: bool,
+    pub pending_fees: bool,
+    /// The orchestrator's `transcoderTotalStake`.
+    pub total_stake: bool,
+    /// Account whose ETH balance is read alongside, e.g. the signer. Best effort: a failed
+    /// balance read leaves `ChainReads::balance` empty instead of failing the loop.
+    pub balance_of: Option<Address>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ChainReads {
+    /// L2 block every value below was read at.
+    pub block: U64,
+    pub state: RoundState,
+    pub schedule: Option<RoundSchedule>,
+    pub last_reward_round: Option<U256>,
+    /// `(activationRound, deactivationRound)`: the orchestrator is active in rounds
+    /// `activation <= round < deactivation`. Read with `last_reward_round`.
+    pub active_rounds: Option<(U256, U256)>,
+    pub pending_stake: Option<U256>,
+    pub pending_fees: Option<U256>,
+    pub total_stake: Option<U256>,
+    pub balance: Option<U256>,
+}
+
+pub struct ChainReader<M> {
+    client: Arc<M>,
+    rounds: RoundsManager<M>,
+    bonding: BondingManager<M>,
+    multicall: Option<Multicall<M>>,
+    orchestrator: Address,
+    plan: ReadPlan,
+    // Round passed to pendingStake/pendingFees. The batch has to encode it before the current
+    // round is known, so it is the round seen last loop and re-read when that turns out stale.
+    last_round: U256,
+}
+
+impl<M: Middleware + 'static> ChainReader<M> {
+    pub fn new(
+        client: Arc<M>,
+        rounds: RoundsManager<M>,
+        bonding: BondingManager<M>,
+        multicall: Option<Multicall<M>>,
+        orchestrator: Address,
+        plan: ReadPlan,
+    ) -> Self {
+        let multicall = multicall.map(|mc| mc.version(MulticallVersion::Multicall3));
+        Self {
+            client,
+            rounds,
+            bonding,
+            multicall,
+            orchestrator,
+            plan,
+            last_round: U256::zero(),
+        }
+    }
+
+    pub async fn fetch(&mut self) -> Result<ChainReads, AppError> {
+        let block = self
+            .client
+            .get_block_number()
+            .await
+            .map_err(|e| AppError::middleware("eth_blockNumber", &e))?;
+
+        let reads = match self.fetch_batched(block).await {
+            Some(Ok(r)) => r,
+            Some(Err(e)) => {
+                warn!(
+                    "multicall read failed at block {block}: {e}; falling back to individual calls"
+                );
+                self.fetch_sequential(block).await?
+            }
+            None => self.fetch_sequential(block).await?,
+        };
+
+        self.last_round = reads.state.round;
+        Ok(reads)
+    }
+
+    /// Returns `None` when no Multicall3 contract is configured for this chain.
+    async fn fetch_batched(&self, block: U64) -> Option<Result<ChainReads, AppError>> {
+        let mut mc = self.multicall.clone()?.block(block);
+        mc.clear_calls();
+
+        mc.add_call(self.rounds.current_round(), false)
+            .add_call(self.rounds.current_round_initialized(), false)
+            .add_call(self.rounds.current_round_locked(), false);
+        if self.plan.schedule {
+            mc.add_call(self.rounds.block_num(), false)
+                .add_call(self.rounds.current_round_start_block(), false)
+                .add_call(self.rounds.round_length(), false)
+                .add_call(self.rounds.round_lock_amount(), false);
+        }
+        if self.plan.transcoder {
+            mc.add_call(self.bonding.get_transcoder(self.orchestrator), false);
+        }
+        if self.plan.pending_stake {
+            mc.add_call(
+                self.bonding
+                    .pending_stake(self.orchestrator, self.last_round),
+                false,
+            );
+        }
+        if self.plan.pending_fees {
+            mc.add_call(
+                self.bonding
+                    .pending_fees(self.orchestrator, self.last_round),
+                false,
+            );
+        }
+        if self.plan.total_stake {
+            mc.add_call(
+                self.bonding.transcoder_total_stake(self.orchestrator),
+                false,
+            );
+        }
+        if let Some(addr) = self.plan.balance_of {
+            mc.add_get_eth_balance(addr, true);
+        }
+
+        let results = match mc.call_raw().await {
+            Ok(r) => r,
+            Err(e) => {
+                return Some(Err(AppError::multicall("Multicall3.aggregate3()", &e)));
+            }
+        };
+
+        Some(self.decode_batch(block, results).await)
+    }
+
+    async fn decode_batch(
+        &self,
+        block: U64,
+        results: Vec<Result<Token, Bytes>>,
+    ) -> Result<ChainReads, AppError> {
+        let mut it = results.into_iter();
+
+        let round = next_uint(&mut it, "currentRound")?;
+        let initialized = next_bool(&mut it, "currentRoundInitialized")?;
+        let locked = next_bool(&mut it, "currentRoundLocked")?;
+        let schedule = if self.plan.schedule {
+            Some(RoundSchedule::from_params(
+                next_uint(&mut it, "blockNum")?,
+                next_uint(&mut it, "currentRoundStartBlock")?,
+                next_uint(&mut it, "roundLength")?,
+                next_uint(&mut it, "roundLockAmount")?,
+            ))
+        } else {
+            None
+        };
+
+        let (last_reward_round, active_rounds) = if self.plan.transcoder {
+            // getTranscoder() returns a tuple: lastRewardRound first, activationRound and
+            // deactivationRound at 4 and 5
+            let fields: Vec<U256> = match it.next() {
+                Some(Ok(Token::Tuple(fields))) => {
+                    fields.into_iter().filter_map(Token::into_uint).collect()
+                }
+                _ => Vec::new(),
+            };
+            if fields.len() < 6 {
+                return Err(AppError::Chain(
+                    ErrorKind::Config,
+                    "multicall: bad getTranscoder result".into(),
+                ));
+            }
+            (Some(fields[0]), Some((fields[4], fields[5])))
+        } else {
+            (None, None)
+        };
+        let mut pending_stake = if self.plan.pending_stake {
+            Some(next_uint(&mut it, "pendingStake")?)
+        } else {
+            None
+        };
+        let mut pending_fees = if self.plan.pending_fees {
+            Some(next_uint(&mut it, "pendingFees")?)
+        } else {
+            None
+        };
+        let total_stake = if self.plan.total_stake {
+            Some(next_uint(&mut it, "transcoderTotalStake")?)
+        } else {
+            None
+        };
+        let balance = match (self.plan.balance_of, it.next()) {
+            (Some(_), Some(Ok(tok))) => tok.into_uint(),
+            _ => None,
+        };
+
+        if round != self.last_round && (self.plan.pending_stake || self.plan.pending_fees) {
+            debug!(
+                "round moved since last loop ({} -> {}); re-reading pending values at block {}",
+                self.last_round, round, block
+            );
+            (pending_stake, pending_fees) = self.read_pending(block, round).await?;
+        }
+
+        Ok(ChainReads {
+            block,
+            state: RoundState {
+                round,
+                initialized,
+                locked,
+            },
+            schedule,
+            last_reward_round,
+            active_rounds,
+            pending_stake,
+            pending_fees,
+            total_stake,
+            balance,
+        })
+    }
+
+    /// The transcoder pool at `block` with each transcoder's total stake, in list order. The
+    /// list can only be walked one `getNextTranscoderInPool` at a time; the stakes then go out
+    /// as one Multicall3 batch, or one by one at the same block without it.
+    pub async fn read_pool(&self, block: U64) -> Result<Vec<(Address, U256)>, AppError> {
+        let mut addrs = Vec::new();
+        let mut cur = self
+            .bonding
+            .get_first_transcoder_in_pool()
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| AppError::contract("BondingManager.getFirstTranscoderInPool()", &e))?;
+        while !cur.is_zero() {
+            addrs.push(cur);
+            cur = self
+                .bonding
+                .get_next_transcoder_in_pool(cur)
+                .block(block)
+                .call()
+                .await
+                .map_err(|e| AppError::contract("BondingManager.getNextTranscoderInPool()", &e))?;
+        }
+
+        if let Some(mc) = &self.multicall
+            && !addrs.is_empty()
+        {
+            let mut mc = mc.clone().block(block);
+            mc.clear_calls();
+            for addr in &addrs {
+                mc.add_call(self.bonding.transcoder_total_stake(*addr), false);
+            }
+            match mc.call_raw().await {
+                Ok(results) => {
+                    let mut it = results.into_iter();
+                    let mut pool = Vec::with_capacity(addrs.len());
+                    for addr in addrs {
+                        pool.push((addr, next_uint(&mut it, "transcoderTotalStake")?));
+                    }
+                    return Ok(pool);
+                }
+                Err(e) => warn!(
+                    "multicall pool read failed at block {block}: {e}; falling back to individual calls"
+                ),
+            }
+        }
+
+        let mut pool = Vec::with_capacity(addrs.len());
+        for addr in addrs {
+            let stake = self
+                .bonding
+                .transcoder_total_stake(addr)
+                .block(block)
+                .call()
+                .await
+                .map_err(|e| AppError::contract("BondingManager.transcoderTotalStake()", &e))?;
+            pool.push((addr, stake));
+        }
+        Ok(pool)
+    }
+
+    async fn fetch_sequential(&self, block: U64) -> Result<ChainReads, AppError> {
+        let round = self
+            .rounds
+            .current_round()
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| AppError::contract("RoundsManager.currentRound()", &e))?;
+
+        let initialized = self
+            .rounds
+            .current_round_initialized()
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| AppError::contract("RoundsManager.currentRoundInitialized()", &e))?;
+
+        let locked = self
+            .rounds
+            .current_round_locked()
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| AppError::contract("RoundsManager.currentRoundLocked()", &e))?;
+
+        let schedule = if self.plan.schedule {
+            Some(self.read_schedule(block).await?)
+        } else {
+            None
+        };
+
+        let (last_reward_round, active_rounds) = if self.plan.transcoder {
+            // getTranscoder(addr) returns a tuple whose first element is lastRewardRound (per ABI)
+            let t = self
+                .bonding
+                .get_transcoder(self.orchestrator)
+                .block(block)
+                .call()
+                .await
+                .map_err(|e| AppError::contract("BondingManager.getTranscoder()", &e))?;
+            (Some(t.0), Some((t.4, t.5)))
+        } else {
+            (None, None)
+        };
+
+        let (pending_stake, pending_fees) = self.read_pending(block, round).await?;
+
+        let total_stake = if self.plan.total_stake {
+            Some(
+                self.bonding
+                    .transcoder_total_stake(self.orchestrator)
+                    .block(block)
+                    .call()
+                    .await
+                    .map_err(|e| AppError::contract("transcoderTotalStake()", &e))?,
+            )
+        } else {
+            None
+        };
+
+        let balance = match self.plan.balance_of {
+            Some(addr) => match self.client.get_balance(addr, Some(block.into())).await {
+                Ok(b) => Some(b),
+                Err(e) => {
+                    warn!("balance read failed at block {block}: addr={addr:?} err={e}");
+                    None
+                }
+            },
+            None => None,
+        };
+
+        Ok(ChainReads {
+            block,
+            state: RoundState {
+                round,
+                initialized,
+                locked,
+            },
+            schedule,
+            last_reward_round,
+            active_rounds,
+            pending_stake,
+            pending_fees,
+            total_stake,
+            balance,
+        })
+    }
+
+    async fn read_schedule(&self, block: U64) -> Result<RoundSchedule, AppError> {
+        let block_num = self
+            .rounds
+            .block_num()
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| AppError::contract("RoundsManager.blockNum()", &e))?;
+
+        let round_start_block = self
+            .rounds
+            .current_round_start_block()
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| AppError::contract("RoundsManager.currentRoundStartBlock()", &e))?;
+
+        let round_length = self
+            .rounds
+            .round_length()
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| AppError::contract("RoundsManager.roundLength()", &e))?;
+
+        let round_lock_amount = self
+            .rounds
+            .round_lock_amount()
+            .block(block)
+            .call()
+            .await
+            .map_err(|e| AppError::contract("RoundsManager.roundLockAmount()", &e))?;
+
+        Ok(RoundSchedule::from_params(
+            block_num,
+            round_start_block,
+            round_length,
+            round_lock_amount,
+        ))
+    }
+
+    async fn read_pending(
+        &self,
+        block: U64,
+        round: U256,
+    ) -> Result<(Option<U256>, Option<U256>), AppError> {
+        let pending_stake = if self.plan.pending_stake {
+            Some(
+                self.bonding
+                    .pending_stake(self.orchestrator, round)
+                    .block(block)
+                    .call()
+                    .await
+                    .map_err(|e| AppError::contract("pendingStake()", &e))?,
+            )
+        } else {
+            None
+        };
+
+        let pending_fees = if self.plan.pending_fees {
+            Some(
+                self.bonding
+                    .pending_fees(self.orchestrator, round)
+                    .block(block)
+                    .call()
+                    .await
+                    .map_err(|e| AppError::contract("pendingFees()", &e))?,
+            )
+        } else {
+            None
+        };
+
+        Ok((pending_stake, pending_fees))
+    }
+}
+
+fn next_uint(
+    it: &mut impl Iterator<Item = Result<Token, Bytes>>,
+    what: &str,
+) -> Result<U256, AppError> {
+    match it.next() {
+        Some(Ok(tok)) => tok.into_uint().ok_or_else(|| {
+            AppError::Chain(ErrorKind::Config, format!("multicall: bad {what} result"))
+        }),
+        _ => Err(AppError::Chain(
+            ErrorKind::Revert,
+            format!("multicall: missing {what} result"),
+        )),
+    }
+}
+
+fn next_bool(
+    it: &mut impl Iterator<Item = Result<Token, Bytes>>,
+    what: &str,
+) -> Result<bool, AppError> {
+    match it.next() {
+        Some(Ok(tok)) => tok.into_bool().ok_or_else(|| {
+            AppError::Chain(ErrorKind::Config, format!("multicall: bad {what} result"))
+        }),
+        _ => Err(AppError::Chain(
+            ErrorKind::Revert,
+            format!("multicall: missing {what} result"),
+        )),
+    }
+}
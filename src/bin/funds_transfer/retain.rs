@@ -0,0 +1,105 @@
+//! How much bonded LPT stays on the orchestrator when the rest is transferred.
+
+use std::fmt;
+
+use ethers::types::U256;
+
+const TOTAL_BPS: u32 = 10_000;
+
+#[derive(Clone, Copy, Debug)]
+pub enum RetainPolicy {
+    /// A fixed amount.
+    Fixed(U256),
+    /// `bps` of pendingStake, and at least `min`.
+    PendingShare { bps: u32, min: U256 },
+    /// Enough self-stake to stay at `bps` of the orchestrator's total stake, and at least
+    /// `min`. Measured against the current total, which a transfer to a receiver delegated
+    /// elsewhere only lowers, so the share holds afterwards too.
+    StakeShare { bps: u32, min: U256 },
+}
+
+impl RetainPolicy {
+    /// Whether `retain` needs the orchestrator's `transcoderTotalStake`.
+    pub fn needs_total_stake(&self) -> bool {
+        matches!(self, RetainPolicy::StakeShare { .. })
+    }
+
+    /// The amount to keep. `total_stake` is only used by `StakeShare`.
+    pub fn retain(&self, pending_stake: U256, total_stake: U256) -> U256 {
+        match *self {
+            RetainPolicy::Fixed(amount) => amount,
+            RetainPolicy::PendingShare { bps, min } => share(pending_stake, bps).max(min),
+            RetainPolicy::StakeShare { bps, min } => share(total_stake, bps).max(min),
+        }
+    }
+}
+
+impl fmt::Display for RetainPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetainPolicy::Fixed(amount) => write!(f, "fixed {amount} wei"),
+            RetainPolicy::PendingShare { bps, min } => {
+                write!(f, "{bps}bps of pendingStake, at least {min} wei")
+            }
+            RetainPolicy::StakeShare { bps, min } => {
+                write!(f, "{bps}bps of transcoderTotalStake, at least {min} wei")
+            }
+        }
+    }
+}
+
+/// `amount * bps / 10000`, rounded up so the share is never undershot. Split into quotient and
+/// remainder so it doesn't overflow for any `amount` with `bps` up to 10000.
+fn share(amount: U256, bps: u32) -> U256 {
+    let total = U256::from(TOTAL_BPS);
+    let bps = U256::from(bps);
+    let whole = (amount / total).saturating_mul(bps);
+    let part = ((amount % total) * bps + total - 1) / total;
+    whole.saturating_add(part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn share_rounds_up() {
+        assert_eq!(share(U256::from(10_000), 2500), U256::from(2500));
+        assert_eq!(share(U256::from(10_001), 2500), U256::from(2501));
+        assert_eq!(share(U256::from(1), 1), U256::one());
+        assert_eq!(share(U256::zero(), 5000), U256::zero());
+    }
+
+    #[test]
+    fn share_does_not_overflow() {
+        assert_eq!(share(U256::MAX, TOTAL_BPS), U256::MAX);
+        assert_eq!(share(U256::MAX, 5000), U256::MAX / 2 + 1);
+        assert_eq!(share(U256::MAX, 1), U256::MAX / U256::from(TOTAL_BPS) + 1);
+    }
+
+    #[test]
+    fn retain_applies_the_min_floor() {
+        let min = U256::from(100);
+        let pending = RetainPolicy::PendingShare { bps: 1000, min };
+        assert_eq!(pending.retain(U256::from(500), U256::zero()), min);
+        assert_eq!(
+            pending.retain(U256::from(5000), U256::zero()),
+            U256::from(500)
+        );
+
+        let stake = RetainPolicy::StakeShare { bps: 1000, min };
+        assert_eq!(stake.retain(U256::from(5000), U256::from(900)), min);
+        assert_eq!(
+            stake.retain(U256::from(5000), U256::from(20_001)),
+            U256::from(2001)
+        );
+        assert!(stake.needs_total_stake());
+        assert!(!pending.needs_total_stake());
+
+        let fixed = RetainPolicy::Fixed(U256::from(42));
+        assert_eq!(
+            fixed.retain(U256::from(5000), U256::from(5000)),
+            U256::from(42)
+        );
+    }
+}
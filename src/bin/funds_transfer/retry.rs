@@ -0,0 +1,489 @@
+//! Error classification and per-action retry backoff.
+//!
+//! Every chain-facing failure is tagged with an [`ErrorKind`] that decides how soon (and
+//! whether) the action is tried again. Each action keeps its own [`Backoff`]: failures back off
+//! exponentially with jitter, and after `RETRY_MAX_ATTEMPTS` failures in a round the action is
+//! left alone until the next round, so a permanently reverting tx isn't resent every loop.
+
+use std::{
+    any::Any,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use ethers::{
+    contract::{ContractError, MulticallError},
+    providers::{JsonRpcError, Middleware, MiddlewareError, ProviderError},
+    types::U256,
+};
+use rand::Rng;
+use tracing::{error, warn};
+
+use crate::{
+    AppError,
+    notify::{Notification, Notifier, NotifyEvent, Severity},
+    rpc::RpcClientError,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Connection failures, timeouts, 5xx, missing receipts. Retried soon.
+    Transport,
+    /// The provider is throttling us. Retried with a longer delay.
+    RateLimit,
+    /// Nonce too low/high or replacement underpriced. Retried once the nonce settles.
+    Nonce,
+    /// The node or the chain rejected the call itself. Retried slowly, within the budget.
+    Revert,
+    /// Bad configuration, ABI mismatch, wallet problems. Not retried this round.
+    Config,
+    /// Too few quorum providers confirmed the values. Retried with a longer delay.
+    Quorum,
+}
+
+impl ErrorKind {
+    pub fn is_retryable(self) -> bool {
+        self != ErrorKind::Config
+    }
+
+    /// Multiplier applied to `RETRY_BASE_SECS`.
+    fn base_factor(self) -> u32 {
+        match self {
+            ErrorKind::Transport | ErrorKind::Nonce => 1,
+            ErrorKind::RateLimit | ErrorKind::Quorum => 5,
+            ErrorKind::Revert => 10,
+            ErrorKind::Config => 0,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ErrorKind::Transport => "transport",
+            ErrorKind::RateLimit => "rate-limit",
+            ErrorKind::Nonce => "nonce",
+            ErrorKind::Revert => "revert",
+            ErrorKind::Config => "config",
+            ErrorKind::Quorum => "quorum",
+        };
+        f.write_str(s)
+    }
+}
+
+impl AppError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            AppError::MissingEnv(_) | AppError::BadEnv(..) | AppError::Wallet(_) => {
+                ErrorKind::Config
+            }
+            AppError::Chain(kind, _) => *kind,
+            // Providers disagreeing or lagging behind usually clears up on its own.
+            AppError::Quorum(_) => ErrorKind::Quorum,
+            // Not a failure of the action; the tx is checked again on the next start.
+            AppError::Interrupted(_) => ErrorKind::Transport,
+        }
+    }
+
+    /// `what` names the call, e.g. `"RoundsManager.currentRound()"`.
+    pub fn contract<M: Middleware + 'static>(what: &str, e: &ContractError<M>) -> Self {
+        AppError::Chain(classify_contract(e), format!("{what} failed: {e}"))
+    }
+
+    pub fn middleware<E: MiddlewareError + 'static>(what: &str, e: &E) -> Self {
+        AppError::Chain(classify_middleware(e), format!("{what} failed: {e}"))
+    }
+
+    pub fn provider(what: &str, e: &ProviderError) -> Self {
+        AppError::Chain(classify_provider(e), format!("{what} failed: {e}"))
+    }
+
+    /// A non-2xx answer from the go-livepeer node; `body` is its error text.
+    pub fn node(what: &str, status: u16, body: &str) -> Self {
+        let kind = match status {
+            429 => ErrorKind::RateLimit,
+            // Missing endpoint or bad parameters: retrying won't fix it.
+            400..=499 => ErrorKind::Config,
+            _ => classify_json_rpc(&JsonRpcError {
+                code: 0,
+                message: body.to_owned(),
+                data: None,
+            }),
+        };
+        AppError::Chain(kind, format!("{what} failed: status={status} body={body}"))
+    }
+
+    pub fn multicall<M: Middleware + 'static>(what: &str, e: &MulticallError<M>) -> Self {
+        let kind = match e.as_contract_error() {
+            Some(ce) => classify_contract(ce),
+            None => ErrorKind::Config,
+        };
+        AppError::Chain(kind, format!("{what} failed: {e}"))
+    }
+}
+
+fn classify_contract<M: Middleware + 'static>(e: &ContractError<M>) -> ErrorKind {
+    match e {
+        ContractError::Revert(_) => ErrorKind::Revert,
+        ContractError::MiddlewareError { e } => classify_middleware(e),
+        ContractError::ProviderError { e } => classify_provider(e),
+        ContractError::DecodingError(_)
+        | ContractError::AbiError(_)
+        | ContractError::DetokenizationError(_)
+        | ContractError::ConstructorError
+        | ContractError::ContractNotDeployed => ErrorKind::Config,
+    }
+}
+
+fn classify_middleware<E: MiddlewareError + 'static>(e: &E) -> ErrorKind {
+    // `as_provider_error()` never finds the provider's own error (it has no inner layer), so
+    // walk down the stack and look for it at each layer.
+    if let Some(p) = (e as &dyn Any).downcast_ref::<ProviderError>() {
+        return classify_provider(p);
+    }
+    if let Some(inner) = e.as_inner() {
+        return classify_middleware(inner);
+    }
+    if let Some(resp) = e.as_error_response() {
+        return classify_json_rpc(resp);
+    }
+    // Errors raised by a middleware layer itself (e.g. the signer).
+    ErrorKind::Config
+}
+
+fn classify_provider(e: &ProviderError) -> ErrorKind {
+    match e {
+        ProviderError::JsonRpcClientError(inner) => {
+            let err: &(dyn std::error::Error + 'static) = inner.as_ref();
+            if let Some(rpc) = err.downcast_ref::<RpcClientError>() {
+                return classify_rpc(rpc);
+            }
+            match inner.as_error_response() {
+                Some(resp) => classify_json_rpc(resp),
+                None => ErrorKind::Transport,
+            }
+        }
+        ProviderError::EnsError(_)
+        | ProviderError::EnsNotOwned(_)
+        | ProviderError::UnsupportedNodeClient
+        | ProviderError::SignerUnavailable
+        | ProviderError::UnsupportedRPC => ErrorKind::Config,
+        _ => ErrorKind::Transport,
+    }
+}
+
+fn classify_rpc(e: &RpcClientError) -> ErrorKind {
+    match e {
+        RpcClientError::JsonRpc(resp) => classify_json_rpc(resp),
+        _ if e.is_rate_limited() => ErrorKind::RateLimit,
+        RpcClientError::Http {
+            status: 401 | 403, ..
+        } => ErrorKind::Config,
+        _ => ErrorKind::Transport,
+    }
+}
+
+fn classify_json_rpc(e: &JsonRpcError) -> ErrorKind {
+    let msg = e.message.to_ascii_lowercase();
+    if crate::rpc::is_rate_limit_error(e) {
+        ErrorKind::RateLimit
+    } else if msg.contains("nonce") || msg.contains("replacement transaction underpriced") {
+        ErrorKind::Nonce
+    } else if e.code == 3 || msg.contains("revert") {
+        ErrorKind::Revert
+    } else if msg.contains("insufficient funds") {
+        // Needs someone to top up the signer.
+        ErrorKind::Config
+    } else {
+        ErrorKind::Transport
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub base_secs: u64,
+    pub max_secs: u64,
+    /// Failed attempts allowed per action per round.
+    pub max_attempts: u32,
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (1-based): `base * factor(kind) * 2^(attempt-1)`,
+    /// capped at `max_secs`, with ±20% jitter so several keepers don't retry in lockstep.
+    pub fn delay(&self, kind: ErrorKind, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(16);
+        let secs = self
+            .base_secs
+            .saturating_mul(u64::from(kind.base_factor().max(1)))
+            .saturating_mul(1u64 << exp)
+            .min(self.max_secs);
+        let jitter = rand::thread_rng().gen_range(0.8..=1.2);
+        Duration::from_secs_f64(secs as f64 * jitter)
+    }
+}
+
+/// Retry state for one action within the current round.
+#[derive(Debug)]
+pub struct Backoff {
+    action: &'static str,
+    round: U256,
+    attempts: u32,
+    next_attempt: Option<Instant>,
+    exhausted: bool,
+    notifier: Notifier,
+}
+
+impl Backoff {
+    pub fn new(action: &'static str, notifier: Notifier) -> Self {
+        Self {
+            action,
+            round: U256::zero(),
+            attempts: 0,
+            next_attempt: None,
+            exhausted: false,
+            notifier,
+        }
+    }
+
+    /// Whether the action may run now. A new round starts with a fresh budget.
+    pub fn ready(&mut self, round: U256) -> bool {
+        if round != self.round {
+            *self = Self {
+                round,
+                ..Self::new(self.action, self.notifier.clone())
+            };
+        }
+        !self.exhausted && self.next_attempt.is_none_or(|t| Instant::now() >= t)
+    }
+
+    pub fn succeeded(&mut self) {
+        self.attempts = 0;
+        self.next_attempt = None;
+    }
+
+    pub fn failed(&mut self, policy: &RetryPolicy, err: &AppError) {
+        // Cut short by shutdown: already logged, and not the action's fault.
+        if let AppError::Interrupted(_) = err {
+            return;
+        }
+        let kind = err.kind();
+        self.attempts += 1;
+        let gave_up = !kind.is_retryable() || self.attempts >= policy.max_attempts;
+
+        if self.notifier.failure_alert_due(self.attempts, gave_up) {
+            let title = match gave_up {
+                true => format!("{} failed, giving up until next round", self.action),
+                false => format!("{} failed {} times in a row", self.action, self.attempts),
+            };
+            let mut n = Notification::new(NotifyEvent::RepeatedFailure, title)
+                .field("action", self.action)
+                .field("round", self.round)
+                .field("attempts", self.attempts)
+                .field("kind", kind)
+                .field("error", err);
+            if gave_up {
+                n = n.severity(Severity::Critical);
+            }
+            self.notifier.notify(n);
+        }
+
+        if gave_up {
+            self.exhausted = true;
+            self.next_attempt = None;
+            error!(
+                event = "action_gave_up",
+                action = self.action,
+                round = %self.round,
+                attempts = self.attempts,
+                status = %kind,
+                err = %err,
+                "{} giving up until next round",
+                self.action
+            );
+            return;
+        }
+
+        let delay = policy.delay(kind, self.attempts);
+        self.next_attempt = Some(Instant::now() + delay);
+        warn!(
+            event = "action_failed",
+            action = self.action,
+            round = %self.round,
+            attempt = self.attempts,
+            max_attempts = policy.max_attempts,
+            status = %kind,
+            retry_in_secs = delay.as_secs(),
+            err = %err,
+            "{} failed",
+            self.action
+        );
+    }
+
+    /// Time left until the next attempt, if one is scheduled in the future.
+    pub fn wait(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.next_attempt
+            .filter(|t| *t > now)
+            .map(|t| t.duration_since(now))
+    }
+}
+
+/// Retry state for every tx-sending action.
+#[derive(Debug)]
+pub struct Retries {
+    pub policy: RetryPolicy,
+    pub reward: Backoff,
+    pub transfer_bond: Backoff,
+    pub withdraw_fees: Backoff,
+}
+
+impl Retries {
+    /// Repeated failures are reported through `notifier`.
+    pub fn new(policy: RetryPolicy, notifier: &Notifier) -> Self {
+        Self {
+            policy,
+            reward: Backoff::new("reward", notifier.clone()),
+            transfer_bond: Backoff::new("transferBond", notifier.clone()),
+            withdraw_fees: Backoff::new("withdrawFees", notifier.clone()),
+        }
+    }
+
+    /// Soonest pending retry across all actions; the loop shouldn't sleep past it.
+    pub fn next_wait(&self) -> Option<Duration> {
+        [&self.reward, &self.transfer_bond, &self.withdraw_fees]
+            .into_iter()
+            .filter_map(Backoff::wait)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpc_error(code: i64, message: &str) -> JsonRpcError {
+        JsonRpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    #[test]
+    fn classifies_json_rpc_errors() {
+        for (code, msg, kind) in [
+            (-32000, "nonce too low", ErrorKind::Nonce),
+            (
+                -32000,
+                "replacement transaction underpriced",
+                ErrorKind::Nonce,
+            ),
+            (3, "execution reverted: ROUND_LOCKED", ErrorKind::Revert),
+            (-32000, "execution reverted", ErrorKind::Revert),
+            (-32005, "request limit", ErrorKind::RateLimit),
+            (-32000, "Too Many Requests", ErrorKind::RateLimit),
+            (
+                -32000,
+                "insufficient funds for gas * price + value",
+                ErrorKind::Config,
+            ),
+            (-32603, "internal error", ErrorKind::Transport),
+        ] {
+            assert_eq!(classify_json_rpc(&rpc_error(code, msg)), kind, "{msg}");
+        }
+    }
+
+    #[test]
+    fn classifies_rpc_client_errors() {
+        let http = |status| RpcClientError::Http {
+            endpoint: "http://rpc".into(),
+            status,
+            body: String::new(),
+        };
+        assert_eq!(classify_rpc(&http(429)), ErrorKind::RateLimit);
+        assert_eq!(classify_rpc(&http(401)), ErrorKind::Config);
+        assert_eq!(classify_rpc(&http(502)), ErrorKind::Transport);
+        let timeout = RpcClientError::Timeout {
+            endpoint: "http://rpc".into(),
+            method: "eth_call".into(),
+            after: Duration::from_secs(10),
+        };
+        assert_eq!(classify_rpc(&timeout), ErrorKind::Transport);
+
+        let e = ProviderError::JsonRpcClientError(Box::new(RpcClientError::JsonRpc(rpc_error(
+            -32000,
+            "nonce too low",
+        ))));
+        assert_eq!(
+            AppError::provider("eth_sendRawTransaction", &e).kind(),
+            ErrorKind::Nonce
+        );
+    }
+
+    #[test]
+    fn classifies_node_answers() {
+        assert_eq!(
+            AppError::node("reward", 429, "").kind(),
+            ErrorKind::RateLimit
+        );
+        assert_eq!(AppError::node("reward", 404, "").kind(), ErrorKind::Config);
+        assert_eq!(
+            AppError::node("reward", 500, "execution reverted").kind(),
+            ErrorKind::Revert
+        );
+        assert_eq!(
+            AppError::node("reward", 503, "unavailable").kind(),
+            ErrorKind::Transport
+        );
+    }
+
+    #[test]
+    fn delay_grows_and_stays_within_bounds() {
+        let policy = RetryPolicy {
+            base_secs: 10,
+            max_secs: 600,
+            max_attempts: 5,
+        };
+        let within = |kind, attempt, secs: f64| {
+            let d = policy.delay(kind, attempt).as_secs_f64();
+            assert!(
+                (secs * 0.8..=secs * 1.2).contains(&d),
+                "{kind} attempt {attempt}: {d}s not within 20% of {secs}s"
+            );
+        };
+        within(ErrorKind::Transport, 1, 10.0);
+        within(ErrorKind::Transport, 3, 40.0);
+        within(ErrorKind::RateLimit, 1, 50.0);
+        within(ErrorKind::Revert, 2, 200.0);
+        within(ErrorKind::Revert, 10, 600.0);
+        within(ErrorKind::Transport, u32::MAX, 600.0);
+        within(ErrorKind::Config, 1, 10.0);
+    }
+
+    #[test]
+    fn backoff_gives_up_for_the_round() {
+        let policy = RetryPolicy {
+            base_secs: 0,
+            max_secs: 0,
+            max_attempts: 2,
+        };
+        let mut b = Backoff::new("reward", Notifier::default());
+        let round = U256::from(5);
+        assert!(b.ready(round));
+        let transport = AppError::Chain(ErrorKind::Transport, "timeout".into());
+        b.failed(&policy, &transport);
+        assert!(b.ready(round));
+        b.failed(&policy, &transport);
+        assert!(!b.ready(round));
+        assert!(b.ready(round + 1), "a new round starts a fresh budget");
+
+        b.failed(&policy, &AppError::Chain(ErrorKind::Config, "bad".into()));
+        assert!(!b.ready(round + 1), "config errors are not retried");
+
+        let mut b = Backoff::new("reward", Notifier::default());
+        assert!(b.ready(round));
+        b.failed(&policy, &AppError::Interrupted("shutdown".into()));
+        b.failed(&policy, &AppError::Interrupted("shutdown".into()));
+        assert!(b.ready(round), "interruptions don't count");
+    }
+}
@@ -0,0 +1,442 @@
+//! Missed-reward detection.
+//!
+//! A round's inflation can only be claimed by calling `reward()` during that round, so a round
+//! that closes without it is lost for good. Whenever rounds close, the ones the orchestrator was
+//! active in are checked: a round counts as rewarded if `lastRewardRound` was seen at it, or if
+//! a `Reward` event for the orchestrator was emitted during it. Rounds not watched live (before
+//! the first start, or while the service was down) are looked up in the `Reward` event history,
+//! mapped to rounds through the RoundsManager `NewRound` events. Every active round after
+//! `lastRewardRound` is missed whatever the history says.
+//!
+//! With `STATE_DIR` set, the last checked round survives restarts, so the rounds missed while
+//! down are checked on the next start; without it the first check looks back
+//! `REWARD_HISTORY_LOOKBACK_BLOCKS`. Missed rounds are logged at ERROR, counted in metrics and
+//! sent as a `missed_reward` notification with the estimated LPT lost, taken from the amount of
+//! the orchestrator's latest `Reward` event.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use ethers::{
+    contract::{EthEvent, parse_log},
+    providers::Middleware,
+    types::{Address, Filter, H256, Log, U256},
+    utils::format_units,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    AppError, NewRoundFilter, RewardFilter,
+    metrics::Metrics,
+    notify::{Notification, Notifier, NotifyEvent},
+    reads::ChainReads,
+};
+
+const STATE_FILE: &str = "reward_watch.json";
+/// A failed history lookup is retried after this long; the loop carries on meanwhile.
+const LOOKUP_RETRY: Duration = Duration::from_secs(300);
+/// Failed lookups in a row before checking against `lastRewardRound` alone.
+const MAX_LOOKUP_FAILURES: u32 = 3;
+/// Missed rounds listed by number in one alert.
+const ROUNDS_LISTED: usize = 20;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RewardWatchConfig {
+    /// How far back the first check looks without saved state.
+    pub lookback_blocks: u64,
+    /// Largest block range per `eth_getLogs` request.
+    pub log_chunk_blocks: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Saved {
+    /// Every round up to and including this one has been checked.
+    checked_round: Option<U256>,
+    /// L2 block that check read at; the round in effect there is `checked_round + 1`.
+    checked_block: Option<u64>,
+    /// Rounds known to be rewarded that haven't been checked yet.
+    rewarded: BTreeSet<U256>,
+    /// Amount of the latest `Reward` event seen, for the loss estimate.
+    last_reward_amount: Option<U256>,
+}
+
+/// What the event history between two blocks says.
+struct History {
+    rewarded: Vec<U256>,
+    /// First round that started inside the range; earlier rounds are only partly covered.
+    first_round_started: Option<U256>,
+    last_amount: Option<U256>,
+}
+
+pub struct RewardWatch<M> {
+    client: Arc<M>,
+    rounds_manager: Address,
+    bonding_manager: Address,
+    orchestrator: Address,
+    cfg: RewardWatchConfig,
+    path: Option<PathBuf>,
+    saved: Saved,
+    lookup_failures: u32,
+    retry_at: Option<Instant>,
+    metrics: Arc<Metrics>,
+    notifier: Notifier,
+}
+
+impl<M: Middleware + 'static> RewardWatch<M> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        client: Arc<M>,
+        rounds_manager: Address,
+        bonding_manager: Address,
+        orchestrator: Address,
+        cfg: RewardWatchConfig,
+        state_dir: Option<&Path>,
+        metrics: Arc<Metrics>,
+        notifier: Notifier,
+    ) -> Self {
+        let path = state_dir.map(|d| d.join(STATE_FILE));
+        let saved: Saved = match path.as_deref().map(fs::read_to_string) {
+            Some(Ok(raw)) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                warn!("ignoring unreadable reward watch file: err={e}");
+                Saved::default()
+            }),
+            _ => Saved::default(),
+        };
+        match saved.checked_round {
+            Some(round) => info!("missed-reward check resumes after round {round}"),
+            None => info!(
+                "missed-reward check starts with a lookback of {} blocks",
+                cfg.lookback_blocks
+            ),
+        }
+        Self {
+            client,
+            rounds_manager,
+            bonding_manager,
+            orchestrator,
+            cfg,
+            path,
+            saved,
+            lookup_failures: 0,
+            retry_at: None,
+            metrics,
+            notifier,
+        }
+    }
+
+    /// Checks the rounds closed since the last call. A failed history lookup is returned and
+    /// tried again later; the rounds stay unchecked until then.
+    pub async fn check(&mut self, reads: &ChainReads) -> Result<(), AppError> {
+        let (Some(last_reward_round), Some((activation, deactivation))) =
+            (reads.last_reward_round, reads.active_rounds)
+        else {
+            return Ok(());
+        };
+        let round = reads.state.round;
+        let changed = !last_reward_round.is_zero() && self.saved.rewarded.insert(last_reward_round);
+
+        let Some(last_closed) = round.checked_sub(U256::one()) else {
+            return Ok(());
+        };
+        if self.saved.checked_round.is_some_and(|c| c >= last_closed) {
+            if changed {
+                self.persist();
+            }
+            return Ok(());
+        }
+        let active = |r: U256| activation <= r && r < deactivation;
+
+        let resume_from = self.saved.checked_round.map(|c| c + 1);
+        let needs_history = match resume_from {
+            Some(from) => {
+                rounds(from, last_closed).any(|r| active(r) && !self.saved.rewarded.contains(&r))
+            }
+            None => true,
+        };
+        let mut history_from = None;
+        if needs_history {
+            if self.retry_at.is_some_and(|t| Instant::now() < t) {
+                return Ok(());
+            }
+            match self.history(reads.block.as_u64()).await {
+                Ok(h) => {
+                    self.lookup_failures = 0;
+                    self.retry_at = None;
+                    self.saved.rewarded.extend(h.rewarded);
+                    if h.last_amount.is_some() {
+                        self.saved.last_reward_amount = h.last_amount;
+                    }
+                    history_from = h.first_round_started;
+                }
+                Err(e) => {
+                    self.lookup_failures += 1;
+                    if self.lookup_failures < MAX_LOOKUP_FAILURES {
+                        self.retry_at = Some(Instant::now() + LOOKUP_RETRY);
+                        return Err(e);
+                    }
+                    warn!(
+                        "reward history unavailable after {} attempts: {e}; checking rounds against lastRewardRound only",
+                        self.lookup_failures
+                    );
+                    self.lookup_failures = 0;
+                    self.retry_at = None;
+                }
+            }
+        }
+
+        // On a first start, only the rounds the history covers can be judged, plus every
+        // round after lastRewardRound.
+        let first = match resume_from {
+            Some(from) => from,
+            None => {
+                let after_last = last_reward_round + 1;
+                history_from.map_or(after_last, |h| h.min(after_last))
+            }
+        };
+        let missed: Vec<U256> = rounds(first, last_closed)
+            .filter(|r| active(*r) && !self.saved.rewarded.contains(r))
+            .collect();
+        if missed.is_empty() {
+            debug!(
+                "missed-reward check: rounds {}..={} rewarded or inactive",
+                first, last_closed
+            );
+        } else {
+            self.report(&missed);
+        }
+
+        self.saved.checked_round = Some(last_closed);
+        self.saved.checked_block = Some(reads.block.as_u64());
+        self.saved.rewarded.retain(|r| *r > last_closed);
+        self.persist();
+        Ok(())
+    }
+
+    fn report(&self, missed: &[U256]) {
+        let mut listed: Vec<String> = missed
+            .iter()
+            .take(ROUNDS_LISTED)
+            .map(ToString::to_string)
+            .collect();
+        if missed.len() > ROUNDS_LISTED {
+            listed.push(format!("… ({} more)", missed.len() - ROUNDS_LISTED));
+        }
+        let listed = listed.join(", ");
+        let estimate = self
+            .saved
+            .last_reward_amount
+            .map(|a| a.saturating_mul(U256::from(missed.len())));
+
+        error!(
+            event = "reward_missed",
+            rounds = %listed,
+            count = missed.len(),
+            estimated_lost_wei = estimate.map(|e| e.to_string()),
+            "reward() was not called in {} round(s); their inflation is lost",
+            missed.len()
+        );
+        self.metrics.rewards_missed(missed.len() as u64, estimate);
+        self.notifier.notify(
+            Notification::new(
+                NotifyEvent::MissedReward,
+                format!("reward missed in {} round(s)", missed.len()),
+            )
+            .field("orchestrator", format!("{:?}", self.orchestrator))
+            .field("rounds", &listed)
+            .field(
+                "estimated_lpt_lost",
+                estimate.map_or_else(|| "unknown".into(), lpt),
+            ),
+        );
+    }
+
+    /// `Reward` and `NewRound` events from after the last check (or the lookback) up to
+    /// `to_block`, in chunks of `log_chunk_blocks`.
+    async fn history(&self, to_block: u64) -> Result<History, AppError> {
+        let from_block = match self.saved.checked_block {
+            Some(b) => b + 1,
+            None => to_block.saturating_sub(self.cfg.lookback_blocks),
+        };
+        let new_round = Filter::new()
+            .address(self.rounds_manager)
+            .topic0(NewRoundFilter::signature());
+        let reward = Filter::new()
+            .address(self.bonding_manager)
+            .topic0(RewardFilter::signature())
+            .topic1(H256::from(self.orchestrator));
+
+        let mut new_rounds: Vec<((u64, u64), U256)> = Vec::new();
+        let mut rewards: Vec<((u64, u64), U256)> = Vec::new();
+        let mut start = from_block;
+        while start <= to_block {
+            let end = start
+                .saturating_add(self.cfg.log_chunk_blocks - 1)
+                .min(to_block);
+            for log in self.logs(&new_round, start, end).await? {
+                if let Ok(ev) = parse_log::<NewRoundFilter>(log.clone()) {
+                    new_rounds.push((position(&log), ev.round));
+                }
+            }
+            for log in self.logs(&reward, start, end).await? {
+                if let Ok(ev) = parse_log::<RewardFilter>(log.clone()) {
+                    rewards.push((position(&log), ev.amount));
+                }
+            }
+            start = end + 1;
+        }
+        new_rounds.sort();
+        rewards.sort();
+        debug!(
+            "reward history: blocks={}..={} new_rounds={} rewards={}",
+            from_block,
+            to_block,
+            new_rounds.len(),
+            rewards.len()
+        );
+
+        let round_before = self.saved.checked_round.map(|c| c + 1);
+        Ok(History {
+            rewarded: rewarded_rounds(&new_rounds, &rewards, round_before),
+            first_round_started: new_rounds.first().map(|(_, round)| *round),
+            last_amount: rewards.last().map(|(_, amount)| *amount),
+        })
+    }
+
+    async fn logs(&self, filter: &Filter, from: u64, to: u64) -> Result<Vec<Log>, AppError> {
+        let filter = filter.clone().from_block(from).to_block(to);
+        self.client
+            .get_logs(&filter)
+            .await
+            .map_err(|e| AppError::middleware("eth_getLogs", &e))
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let tmp = path.with_extension("json.tmp");
+        let res = serde_json::to_vec_pretty(&self.saved)
+            .map_err(|e| e.to_string())
+            .and_then(|data| fs::write(&tmp, data).map_err(|e| e.to_string()))
+            .and_then(|()| fs::rename(&tmp, path).map_err(|e| e.to_string()));
+        if let Err(e) = res {
+            warn!(
+                "failed to write reward watch file: path={} err={e}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// The round each reward was called in: the last one started before it. Before the first
+/// `NewRound` in range, that is `round_before` (the round in effect at the previous check), if
+/// known. Both lists are sorted by position.
+fn rewarded_rounds(
+    new_rounds: &[((u64, u64), U256)],
+    rewards: &[((u64, u64), U256)],
+    round_before: Option<U256>,
+) -> Vec<U256> {
+    rewards
+        .iter()
+        .filter_map(|(pos, _)| {
+            new_rounds
+                .iter()
+                .rev()
+                .find(|(started, _)| started < pos)
+                .map(|(_, round)| *round)
+                .or(round_before)
+        })
+        .collect()
+}
+
+/// `from..=to`, empty when `from > to`.
+fn rounds(from: U256, to: U256) -> impl Iterator<Item = U256> {
+    let mut next = from;
+    std::iter::from_fn(move || {
+        if next > to {
+            return None;
+        }
+        let r = next;
+        next += U256::one();
+        Some(r)
+    })
+}
+
+/// Where a log sits in the chain, for ordering.
+fn position(log: &Log) -> (u64, u64) {
+    (
+        log.block_number.map(|b| b.as_u64()).unwrap_or_default(),
+        log.log_index.map(|i| i.low_u64()).unwrap_or_default(),
+    )
+}
+
+/// Wei as LPT with at most 4 decimals.
+fn lpt(wei: U256) -> String {
+    let s = format_units(wei, 18).unwrap_or_else(|_| wei.to_string());
+    match s.split_once('.') {
+        Some((int, frac)) => {
+            let frac = frac[..frac.len().min(4)].trim_end_matches('0');
+            if frac.is_empty() {
+                format!("{int} LPT")
+            } else {
+                format!("{int}.{frac} LPT")
+            }
+        }
+        None => format!("{s} LPT"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn r(n: u64) -> U256 {
+        U256::from(n)
+    }
+
+    #[test]
+    fn maps_rewards_to_the_round_started_before_them() {
+        let new_rounds = [((100, 0), r(7)), ((200, 3), r(8)), ((300, 0), r(9))];
+        let rewards = [
+            ((50, 0), r(1)),
+            ((100, 1), r(1)),
+            ((200, 2), r(1)),
+            ((200, 4), r(1)),
+            ((400, 0), r(1)),
+        ];
+        assert_eq!(
+            rewarded_rounds(&new_rounds, &rewards, Some(r(6))),
+            vec![r(6), r(7), r(7), r(8), r(9)]
+        );
+        // Without an earlier check, a reward before the first NewRound can't be placed.
+        assert_eq!(
+            rewarded_rounds(&new_rounds, &rewards[..2], None),
+            vec![r(7)]
+        );
+        assert_eq!(rewarded_rounds(&[], &rewards[..1], Some(r(6))), vec![r(6)]);
+    }
+
+    #[test]
+    fn rounds_are_inclusive() {
+        assert_eq!(
+            rounds(r(3), r(5)).collect::<Vec<_>>(),
+            vec![r(3), r(4), r(5)]
+        );
+        assert_eq!(rounds(r(5), r(5)).count(), 1);
+        assert_eq!(rounds(r(6), r(5)).count(), 0);
+    }
+
+    #[test]
+    fn formats_lpt() {
+        assert_eq!(lpt(U256::exp10(18)), "1 LPT");
+        assert_eq!(lpt(U256::from(1_234_567_890_000_000_000u64)), "1.2345 LPT");
+        assert_eq!(lpt(U256::from(500_000_000_000_000_000u64)), "0.5 LPT");
+        assert_eq!(lpt(U256::zero()), "0 LPT");
+    }
+}
@@ -0,0 +1,226 @@
+//! Block-aware loop scheduling.
+//!
+//! Livepeer rounds are measured in L1 block numbers (`RoundsManager.blockNum()` returns the
+//! L1 block on Arbitrum). Once the actions for the current phase are done there is nothing to
+//! do until the lock window opens or the next round starts, so instead of polling every
+//! `LOOP_SLEEP_SECS` we sleep until shortly before the next phase change.
+
+use std::time::Duration;
+
+use ethers::types::U256;
+use tracing::debug;
+
+use crate::RoundState;
+
+/// Divisor for `roundLockAmount`, which is a percentage expressed in parts per million.
+const PERC_DIVISOR: u64 = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedulerMode {
+    /// Poll every `LOOP_SLEEP_SECS` (default).
+    Fixed,
+    /// Sleep until shortly before the next lock window / round start.
+    Block,
+}
+
+impl SchedulerMode {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "fixed" => Some(SchedulerMode::Fixed),
+            "block" => Some(SchedulerMode::Block),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SchedulerConfig {
+    pub mode: SchedulerMode,
+    /// Fixed polling interval; also the floor for adaptive sleeps.
+    pub loop_sleep_secs: u64,
+    /// Expected seconds per L1 block.
+    pub l1_block_time_secs: u64,
+    /// Wake this many seconds before a phase change.
+    pub wake_margin_secs: u64,
+    /// Upper bound for a single adaptive sleep.
+    pub max_sleep_secs: u64,
+}
+
+/// Round boundaries in L1 blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoundSchedule {
+    pub block_num: U256,
+    pub round_start_block: U256,
+    pub lock_start_block: U256,
+    pub next_round_start_block: U256,
+}
+
+impl RoundSchedule {
+    pub fn from_params(
+        block_num: U256,
+        round_start_block: U256,
+        round_length: U256,
+        round_lock_amount: U256,
+    ) -> Self {
+        // Mirrors RoundsManager.currentRoundLocked():
+        //   blockNum() >= currentRoundStartBlock() + roundLength - percOf(roundLength, roundLockAmount)
+        let lock_blocks = round_length * round_lock_amount / U256::from(PERC_DIVISOR);
+        let next_round_start_block = round_start_block + round_length;
+        Self {
+            block_num,
+            round_start_block,
+            lock_start_block: next_round_start_block.saturating_sub(lock_blocks),
+            next_round_start_block,
+        }
+    }
+
+    /// Next phase boundary strictly after the current block.
+    pub fn next_phase_block(&self) -> U256 {
+        if self.block_num < self.lock_start_block {
+            self.lock_start_block
+        } else {
+            self.next_round_start_block
+        }
+    }
+}
+
+/// Decide how long to sleep before the next loop iteration.
+///
+/// `settled` means every enabled action for the current phase is done (nothing sent, nothing
+/// failed). Anything else falls back to the fixed interval so retries and confirmations keep
+/// their current cadence.
+pub fn next_sleep(
+    cfg: &SchedulerConfig,
+    state: &RoundState,
+    schedule: Option<&RoundSchedule>,
+    settled: bool,
+) -> Duration {
+    let fixed = Duration::from_secs(cfg.loop_sleep_secs);
+
+    if cfg.mode == SchedulerMode::Fixed || !settled {
+        return fixed;
+    }
+
+    // The round can start (by block number) before anyone calls initializeRound(); keep
+    // polling until it is initialized so reward() goes out promptly.
+    if !state.initialized {
+        return fixed;
+    }
+
+    let Some(schedule) = schedule else {
+        return fixed;
+    };
+
+    let target = schedule.next_phase_block();
+    let blocks_left = target.saturating_sub(schedule.block_num);
+    let secs_left = blocks_left
+        .saturating_mul(U256::from(cfg.l1_block_time_secs))
+        .min(U256::from(u64::MAX))
+        .as_u64();
+
+    let secs = secs_left.saturating_sub(cfg.wake_margin_secs).clamp(
+        cfg.loop_sleep_secs,
+        cfg.max_sleep_secs.max(cfg.loop_sleep_secs),
+    );
+
+    debug!(
+        "scheduler: block={} next_phase_block={} blocks_left={} sleep_secs={}",
+        schedule.block_num, target, blocks_left, secs
+    );
+
+    Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CFG: SchedulerConfig = SchedulerConfig {
+        mode: SchedulerMode::Block,
+        loop_sleep_secs: 60,
+        l1_block_time_secs: 12,
+        wake_margin_secs: 60,
+        max_sleep_secs: 1800,
+    };
+
+    fn state(initialized: bool) -> RoundState {
+        RoundState {
+            round: U256::from(100),
+            initialized,
+            locked: false,
+        }
+    }
+
+    /// Mainnet-like round: 5760 blocks, the last 10% locked.
+    fn schedule(block: u64) -> RoundSchedule {
+        RoundSchedule::from_params(
+            U256::from(block),
+            U256::from(1000),
+            U256::from(5760),
+            U256::from(100_000),
+        )
+    }
+
+    #[test]
+    fn lock_window_mirrors_the_contract() {
+        let s = schedule(1000);
+        assert_eq!(s.lock_start_block, U256::from(1000 + 5760 - 576));
+        assert_eq!(s.next_round_start_block, U256::from(6760));
+        assert_eq!(s.next_phase_block(), s.lock_start_block);
+        assert_eq!(schedule(6183).next_phase_block(), U256::from(6184));
+        assert_eq!(schedule(6184).next_phase_block(), U256::from(6760));
+    }
+
+    #[test]
+    fn sleeps_until_shortly_before_the_next_phase() {
+        // 10 blocks to the lock window: 120s minus the 60s margin.
+        let s = schedule(6174);
+        assert_eq!(
+            next_sleep(&CFG, &state(true), Some(&s), true),
+            Duration::from_secs(60)
+        );
+        let s = schedule(6164);
+        assert_eq!(
+            next_sleep(&CFG, &state(true), Some(&s), true),
+            Duration::from_secs(180)
+        );
+        // Never below the fixed interval, never above the cap.
+        let s = schedule(6183);
+        assert_eq!(
+            next_sleep(&CFG, &state(true), Some(&s), true),
+            Duration::from_secs(60)
+        );
+        let s = schedule(1000);
+        assert_eq!(
+            next_sleep(&CFG, &state(true), Some(&s), true),
+            Duration::from_secs(1800)
+        );
+        // Inside the lock window the target is the next round start.
+        let s = schedule(6740);
+        assert_eq!(
+            next_sleep(&CFG, &state(true), Some(&s), true),
+            Duration::from_secs(180)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_fixed_interval() {
+        let fixed = Duration::from_secs(CFG.loop_sleep_secs);
+        let s = schedule(1000);
+        assert_eq!(next_sleep(&CFG, &state(true), Some(&s), false), fixed);
+        assert_eq!(next_sleep(&CFG, &state(false), Some(&s), true), fixed);
+        assert_eq!(next_sleep(&CFG, &state(true), None, true), fixed);
+        let cfg = SchedulerConfig {
+            mode: SchedulerMode::Fixed,
+            ..CFG
+        };
+        assert_eq!(next_sleep(&cfg, &state(true), Some(&s), true), fixed);
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(SchedulerMode::parse(" Block "), Some(SchedulerMode::Block));
+        assert_eq!(SchedulerMode::parse("fixed"), Some(SchedulerMode::Fixed));
+        assert_eq!(SchedulerMode::parse("adaptive"), None);
+    }
+}
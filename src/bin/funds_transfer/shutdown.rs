@@ -0,0 +1,116 @@
+//! Graceful shutdown on SIGTERM / SIGINT.
+//!
+//! The first signal stops the loop from starting new actions and wakes it from any sleep. A tx
+//! already sent gets `SHUTDOWN_GRACE_SECS` to confirm; after that its hash stays in the
+//! in-flight file and is picked up on the next start. A second signal exits immediately.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::{Notify, watch};
+use tracing::{info, warn};
+
+/// Clean shutdown, nothing left in flight.
+pub const EXIT_OK: u8 = 0;
+/// Generic fatal error.
+pub const EXIT_FAILURE: u8 = 1;
+/// Stopped with a tx still unconfirmed (EX_TEMPFAIL); it is checked again on the next start.
+pub const EXIT_INFLIGHT: u8 = 75;
+/// Invalid configuration (EX_CONFIG); restarting won't help.
+pub const EXIT_CONFIG: u8 = 78;
+/// Forced by a second signal (128 + SIGINT).
+pub const EXIT_FORCED: i32 = 130;
+
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    rx: watch::Receiver<bool>,
+    grace: Duration,
+}
+
+impl Shutdown {
+    /// Install the signal handlers. `wake` is notified so the loop doesn't sleep through the
+    /// request.
+    pub fn listen(grace: Duration, wake: Arc<Notify>) -> Self {
+        let (tx, rx) = watch::channel(false);
+
+        tokio::spawn(async move {
+            let mut signals = Signals::new();
+            let first = signals.recv().await;
+            info!(
+                "{first} received: shutting down (no new actions, grace_secs={})",
+                grace.as_secs()
+            );
+            let _ = tx.send(true);
+            wake.notify_one();
+
+            let second = signals.recv().await;
+            warn!("{second} received again: exiting immediately");
+            std::process::exit(EXIT_FORCED);
+        });
+
+        Self { rx, grace }
+    }
+
+    pub fn requested(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Resolves `grace` after shutdown was requested; never resolves otherwise.
+    pub async fn grace_expired(&self) {
+        let mut rx = self.rx.clone();
+        if rx.wait_for(|stop| *stop).await.is_err() {
+            // Sender gone without a request: nothing will ever ask us to stop.
+            std::future::pending::<()>().await;
+        }
+        tokio::time::sleep(self.grace).await;
+    }
+}
+
+#[cfg(unix)]
+struct Signals {
+    term: Option<tokio::signal::unix::Signal>,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> Self {
+        use tokio::signal::unix::{SignalKind, signal};
+        let term = match signal(SignalKind::terminate()) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                warn!("failed to install SIGTERM handler: {e}");
+                None
+            }
+        };
+        Self { term }
+    }
+
+    async fn recv(&mut self) -> &'static str {
+        let term = async {
+            match self.term.as_mut() {
+                Some(s) => {
+                    s.recv().await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = term => "SIGTERM",
+            _ = tokio::signal::ctrl_c() => "SIGINT",
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Signals;
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> Self {
+        Signals
+    }
+
+    async fn recv(&mut self) -> &'static str {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl+C"
+    }
+}
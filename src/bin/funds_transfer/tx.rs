@@ -0,0 +1,716 @@
+//! Sending txs and tracking the ones still in flight.
+//!
+//! A tx is recorded in `STATE_DIR/inflight.json` as soon as it is broadcast and removed once
+//! it is mined or dropped. If the process stops in between (shutdown grace expired, crash,
+//! SIGKILL), the next start waits for those txs before doing anything else. One that is still
+//! pending after that (or after a receipt timeout) blocks its action until it lands or is
+//! dropped, since a resend would take a new nonce and pay twice.
+//!
+//! With a keeper lease, nothing is sent while the lease isn't held.
+//!
+//! The key may be shared with go-livepeer, which sends its own reward and ticket redemption
+//! txs. Every tx therefore takes its nonce from the signer's *pending* count, and a nonce
+//! conflict (`nonce too low`, `replacement transaction underpriced`) is retried with a fresh
+//! nonce instead of bumping the fee to replace the other sender's tx.
+//!
+//! With a node executor nothing is signed here; actions go to the go-livepeer node instead.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ethers::{
+    abi::Detokenize,
+    contract::ContractCall,
+    providers::{JsonRpcClient, Middleware, PendingTransaction},
+    types::{BlockNumber, TransactionReceipt, TxHash, U64, U256},
+};
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep, timeout};
+use tracing::{Instrument, Span, field, info, info_span, warn};
+
+use crate::{
+    AppError,
+    lease::LeaseWatch,
+    metrics::{Metrics, TxOutcome},
+    node::{NodeAction, NodeClient},
+    notify::{Notification, Notifier, NotifyEvent},
+    payout::PayoutStore,
+    retry::{ErrorKind, Retries},
+    shutdown::Shutdown,
+};
+
+const INFLIGHT_FILE: &str = "inflight.json";
+const RECOVER_POLL: Duration = Duration::from_secs(2);
+/// In-place resends after a nonce conflict; the retry backoff takes over after that.
+const NONCE_RETRIES: u32 = 2;
+const NONCE_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// Recent tx hashes kept to tell our own events from other senders'.
+const OWN_TXS_KEPT: usize = 64;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InflightTx {
+    pub action: String,
+    pub round: U256,
+    pub tx_hash: TxHash,
+    /// Unix seconds.
+    pub sent_at: u64,
+}
+
+/// In-memory list of unconfirmed txs, mirrored to disk when a state dir is configured.
+#[derive(Debug)]
+struct InflightStore {
+    path: Option<PathBuf>,
+    txs: Mutex<Vec<InflightTx>>,
+}
+
+impl InflightStore {
+    fn open(state_dir: Option<&Path>) -> Self {
+        Self {
+            path: state_dir.map(|d| d.join(INFLIGHT_FILE)),
+            txs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Re-read the file; another instance may have written it while we were in standby.
+    fn reload(&self) {
+        let txs = match self.path.as_deref().map(fs::read_to_string) {
+            Some(Ok(raw)) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                warn!("ignoring unreadable in-flight file: err={e}");
+                Vec::new()
+            }),
+            _ => Vec::new(),
+        };
+        *self.txs.lock().unwrap_or_else(|e| e.into_inner()) = txs;
+    }
+
+    fn snapshot(&self) -> Vec<InflightTx> {
+        self.txs.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn add(&self, tx: InflightTx) {
+        let mut txs = self.txs.lock().unwrap_or_else(|e| e.into_inner());
+        txs.push(tx);
+        self.persist(&txs);
+    }
+
+    fn remove(&self, tx_hash: TxHash) {
+        let mut txs = self.txs.lock().unwrap_or_else(|e| e.into_inner());
+        txs.retain(|t| t.tx_hash != tx_hash);
+        self.persist(&txs);
+    }
+
+    fn persist(&self, txs: &[InflightTx]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let tmp = path.with_extension("json.tmp");
+        let res = serde_json::to_vec_pretty(txs)
+            .map_err(|e| e.to_string())
+            .and_then(|data| fs::write(&tmp, data).map_err(|e| e.to_string()))
+            .and_then(|()| fs::rename(&tmp, path).map_err(|e| e.to_string()));
+        if let Err(e) = res {
+            warn!(
+                "failed to write in-flight file: path={} err={e}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// Hashes of txs this process sent recently, shared with the event listener.
+#[derive(Clone, Debug, Default)]
+pub struct OwnTxs(Arc<Mutex<VecDeque<TxHash>>>);
+
+impl OwnTxs {
+    fn record(&self, tx_hash: TxHash) {
+        let mut txs = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if txs.len() >= OWN_TXS_KEPT {
+            txs.pop_front();
+        }
+        txs.push_back(tx_hash);
+    }
+
+    /// The most recent one; a change across a send means that send was broadcast.
+    pub fn latest(&self) -> Option<TxHash> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .back()
+            .copied()
+    }
+
+    pub fn contains(&self, tx_hash: TxHash) -> bool {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(&tx_hash)
+    }
+}
+
+pub struct TxSender {
+    pub retries: Retries,
+    /// Split payouts with legs still to pay.
+    pub payouts: PayoutStore,
+    receipt_timeout: Duration,
+    shutdown: Shutdown,
+    inflight: InflightStore,
+    lease: Option<LeaseWatch>,
+    own: OwnTxs,
+    node: Option<NodeClient>,
+    metrics: Arc<Metrics>,
+    notifier: Notifier,
+}
+
+impl TxSender {
+    pub fn new(
+        retries: Retries,
+        receipt_timeout_secs: u64,
+        shutdown: Shutdown,
+        state_dir: Option<&Path>,
+    ) -> Self {
+        Self {
+            retries,
+            payouts: PayoutStore::open(state_dir),
+            receipt_timeout: Duration::from_secs(receipt_timeout_secs),
+            shutdown,
+            inflight: InflightStore::open(state_dir),
+            lease: None,
+            own: OwnTxs::default(),
+            node: None,
+            metrics: Arc::default(),
+            notifier: Notifier::default(),
+        }
+    }
+
+    /// Record tx outcomes and gas in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Send tx sent/confirmed/reverted notifications through `notifier`.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Refuse to send while `lease` isn't held.
+    pub fn with_lease(mut self, lease: Option<LeaseWatch>) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// Send actions through a go-livepeer node instead of signing them here.
+    pub fn with_node(mut self, node: Option<NodeClient>) -> Self {
+        self.node = node;
+        self
+    }
+
+    pub fn uses_node(&self) -> bool {
+        self.node.is_some()
+    }
+
+    /// Shutdown was requested; don't start anything new.
+    pub fn stopping(&self) -> bool {
+        self.shutdown.requested()
+    }
+
+    pub fn own_txs(&self) -> OwnTxs {
+        self.own.clone()
+    }
+
+    pub fn inflight_count(&self) -> usize {
+        self.inflight.snapshot().len()
+    }
+
+    /// Wait for txs left unconfirmed by a previous run (or a previous lease holder) before the
+    /// loop may send new ones. One still pending after the receipt timeout stays recorded, and
+    /// its action isn't sent again until it is mined or dropped.
+    pub async fn recover<M: Middleware + 'static>(&self, client: &M) {
+        self.inflight.reload();
+        for tx in self.inflight.snapshot() {
+            info!(
+                "checking in-flight tx from previous run: action={} round={} tx_hash={:?} sent_at={}",
+                tx.action, tx.round, tx.tx_hash, tx.sent_at
+            );
+
+            let started = Instant::now();
+            loop {
+                match inflight_state(client, tx.tx_hash).await {
+                    Ok(InflightState::Mined { status, block }) => {
+                        info!(
+                            event = "inflight_settled",
+                            action = %tx.action,
+                            round = %tx.round,
+                            tx_hash = ?tx.tx_hash,
+                            status = receipt_status(status),
+                            block = block.map(|b| b.as_u64()),
+                            "in-flight tx settled"
+                        );
+                        self.inflight.remove(tx.tx_hash);
+                        break;
+                    }
+                    Ok(InflightState::Dropped) => {
+                        warn!(
+                            "in-flight tx dropped: action={} round={} tx_hash={:?}",
+                            tx.action, tx.round, tx.tx_hash
+                        );
+                        self.inflight.remove(tx.tx_hash);
+                        break;
+                    }
+                    Ok(InflightState::Pending) => {}
+                    Err(e) => warn!(
+                        "in-flight tx lookup failed: tx_hash={:?} err={e}",
+                        tx.tx_hash
+                    ),
+                }
+
+                if started.elapsed() >= self.receipt_timeout || self.stopping() {
+                    warn!(
+                        "in-flight tx still unconfirmed after {}s: action={} tx_hash={:?}; {} won't be sent again until it is mined or dropped",
+                        started.elapsed().as_secs(),
+                        tx.action,
+                        tx.tx_hash,
+                        tx.action
+                    );
+                    break;
+                }
+                sleep(RECOVER_POLL).await;
+            }
+
+            if self.stopping() {
+                return;
+            }
+        }
+    }
+
+    /// Errors while an earlier `action` tx is still pending; new txs take the pending nonce, so
+    /// sending now would not replace it but pay twice. Mined or dropped ones are cleared.
+    async fn check_inflight<M: Middleware + 'static>(
+        &self,
+        client: &M,
+        action: &str,
+        round: U256,
+    ) -> Result<(), AppError> {
+        for tx in self.inflight.snapshot() {
+            if tx.action != action {
+                continue;
+            }
+            match inflight_state(client, tx.tx_hash).await? {
+                InflightState::Mined { status, .. } => {
+                    info!(
+                        "earlier {action} tx settled: round={} tx_hash={:?} status={}",
+                        tx.round,
+                        tx.tx_hash,
+                        receipt_status(status)
+                    );
+                    self.inflight.remove(tx.tx_hash);
+                }
+                InflightState::Dropped => {
+                    warn!(
+                        "earlier {action} tx dropped: round={} tx_hash={:?}",
+                        tx.round, tx.tx_hash
+                    );
+                    self.inflight.remove(tx.tx_hash);
+                }
+                InflightState::Pending => {
+                    return Err(AppError::Chain(
+                        ErrorKind::Nonce,
+                        format!(
+                            "{action} not sent: earlier tx still pending: round={round} tx_hash={:?}",
+                            tx.tx_hash
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends `call` with the signer's pending nonce and waits for its receipt with a timeout so
+    /// we don't hang forever. A mined tx with status 0 is reported as a revert. On shutdown the
+    /// receipt wait is cut short after the grace period and the tx stays recorded as in flight.
+    pub async fn send_and_confirm<M: Middleware + 'static, D: Detokenize>(
+        &self,
+        client: &M,
+        action: &str,
+        call: ContractCall<M, D>,
+        round: U256,
+    ) -> Result<(), AppError> {
+        self.check_lease(action, round)?;
+        self.check_inflight(client, action, round).await?;
+        let span = tx_span(action, round);
+        let res = self
+            .send_with_pending_nonce(client, action, call, round)
+            .instrument(span.clone())
+            .await;
+        record_result(&span, &res);
+        res
+    }
+
+    async fn send_with_pending_nonce<M: Middleware + 'static, D: Detokenize>(
+        &self,
+        client: &M,
+        action: &str,
+        mut call: ContractCall<M, D>,
+        round: U256,
+    ) -> Result<(), AppError> {
+        let mut attempt = 0;
+        loop {
+            if let Some(nonce) = pending_nonce(client).await? {
+                call.tx.set_nonce(nonce);
+            }
+
+            let err = match call.send().await {
+                Ok(pending) => return self.confirm(action, pending, round).await,
+                Err(e) => AppError::contract(&format!("{action}() send"), &e),
+            };
+            if err.kind() != ErrorKind::Nonce || attempt >= NONCE_RETRIES || self.stopping() {
+                self.metrics.tx_outcome(action, TxOutcome::SendFailed);
+                return Err(err);
+            }
+
+            // Another sender took the nonce between our read and the broadcast. If its tx did
+            // what we were about to do, the resend fails gas estimation and nothing is sent.
+            attempt += 1;
+            warn!(
+                "{action} nonce conflict with another sender using the key: round={} nonce={:?} attempt={}/{} retry_in_secs={} err={}",
+                round,
+                call.tx.nonce(),
+                attempt,
+                NONCE_RETRIES,
+                NONCE_RETRY_DELAY.as_secs(),
+                err
+            );
+            sleep(NONCE_RETRY_DELAY).await;
+        }
+    }
+
+    /// Has the node send `action` and waits for its answer (the node replies once mined).
+    pub async fn send_via_node(&self, action: NodeAction, round: U256) -> Result<(), AppError> {
+        let name = action.name();
+        let Some(node) = &self.node else {
+            return Err(AppError::Chain(
+                ErrorKind::Config,
+                format!("{name}: no node executor configured"),
+            ));
+        };
+        self.check_lease(name, round)?;
+
+        let span = tx_span(name, round);
+        self.notifier.notify(
+            Notification::new(NotifyEvent::TxSent, format!("{name} requested from node"))
+                .field("action", name)
+                .field("round", round),
+        );
+        let res = tokio::select! {
+            r = node.execute(&action, round).instrument(span.clone()) => r,
+            _ = self.shutdown.grace_expired() => {
+                warn!("{name} node request still pending at shutdown: round={round}");
+                Err(AppError::Interrupted(format!(
+                    "{name} node request cut short by shutdown"
+                )))
+            }
+        };
+        // The node doesn't report gas; only the outcome is recorded.
+        let outcome = match &res {
+            Ok(()) => TxOutcome::Confirmed,
+            Err(AppError::Interrupted(_)) => TxOutcome::Interrupted,
+            Err(e) if e.kind() == ErrorKind::Revert => TxOutcome::Reverted,
+            Err(_) => TxOutcome::SendFailed,
+        };
+        self.metrics.tx_outcome(name, outcome);
+        match outcome {
+            TxOutcome::Confirmed => self.notifier.notify(
+                Notification::new(
+                    NotifyEvent::TxConfirmed,
+                    format!("{name} confirmed by node"),
+                )
+                .field("action", name)
+                .field("round", round),
+            ),
+            TxOutcome::Reverted => self.notifier.notify(
+                Notification::new(NotifyEvent::TxReverted, format!("{name} reverted"))
+                    .field("action", name)
+                    .field("round", round)
+                    .field(
+                        "error",
+                        res.as_ref()
+                            .err()
+                            .map(ToString::to_string)
+                            .unwrap_or_default(),
+                    ),
+            ),
+            _ => {}
+        }
+        record_result(&span, &res);
+        res
+    }
+
+    fn check_lease(&self, action: &str, round: U256) -> Result<(), AppError> {
+        if let Some(lease) = &self.lease
+            && !lease.held()
+        {
+            warn!("{action} not sent: keeper lease not held: round={round}");
+            return Err(AppError::Interrupted(format!(
+                "{action} not sent: keeper lease not held"
+            )));
+        }
+        Ok(())
+    }
+
+    async fn confirm<P: JsonRpcClient>(
+        &self,
+        action: &str,
+        pending: PendingTransaction<'_, P>,
+        round: U256,
+    ) -> Result<(), AppError> {
+        let tx_hash = *pending;
+        Span::current().record("tx_hash", field::debug(tx_hash));
+        self.own.record(tx_hash);
+        info!(
+            event = "tx_sent",
+            action,
+            round = %round,
+            tx_hash = ?tx_hash,
+            "{action} tx sent"
+        );
+        self.notifier.notify(
+            Notification::new(NotifyEvent::TxSent, format!("{action} tx sent"))
+                .field("action", action)
+                .field("round", round)
+                .field("tx_hash", format!("{tx_hash:?}")),
+        );
+        self.inflight.add(InflightTx {
+            action: action.to_owned(),
+            round,
+            tx_hash,
+            sent_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        });
+
+        // The read endpoints may see the tx later than the endpoint it was broadcast to; keep
+        // polling and let the receipt timeout decide instead of giving up after a few misses.
+        let pending = pending.retries(usize::MAX);
+
+        let outcome = tokio::select! {
+            r = timeout(self.receipt_timeout, pending) => r,
+            _ = self.shutdown.grace_expired() => {
+                warn!(
+                    event = "tx_unconfirmed",
+                    action,
+                    round = %round,
+                    tx_hash = ?tx_hash,
+                    status = "interrupted",
+                    persisted = self.inflight.path.is_some(),
+                    "{action} tx unconfirmed at shutdown"
+                );
+                self.metrics.tx_outcome(action, TxOutcome::Interrupted);
+                return Err(AppError::Interrupted(format!(
+                    "{action} receipt wait cut short by shutdown: tx_hash={tx_hash:?}"
+                )));
+            }
+        };
+        // Without an answer the tx may still land; the entry blocks a resend until it does
+        // or is dropped.
+        if matches!(outcome, Ok(Ok(_))) {
+            self.inflight.remove(tx_hash);
+        }
+
+        let outcome_metric = match &outcome {
+            Ok(Ok(Some(receipt))) => {
+                let span = Span::current();
+                if let Some(gas_used) = receipt.gas_used {
+                    span.record("gas_used", gas_used.low_u64());
+                }
+                if let Some(block) = receipt.block_number {
+                    span.record("block", block.as_u64());
+                }
+                self.metrics
+                    .gas(action, receipt.gas_used, receipt.effective_gas_price);
+                if receipt.status == Some(U64::zero()) {
+                    TxOutcome::Reverted
+                } else {
+                    TxOutcome::Confirmed
+                }
+            }
+            _ => TxOutcome::Unconfirmed,
+        };
+        self.metrics.tx_outcome(action, outcome_metric);
+
+        let receipt_timeout_secs = self.receipt_timeout.as_secs();
+        match outcome {
+            Ok(Ok(Some(receipt))) if receipt.status == Some(U64::zero()) => {
+                warn!(
+                    event = "tx_reverted",
+                    action,
+                    round = %round,
+                    tx_hash = ?receipt.transaction_hash,
+                    status = "reverted",
+                    block = receipt.block_number.map(|b| b.as_u64()),
+                    gas_used = receipt.gas_used.map(|g| g.low_u64()),
+                    "{action} tx reverted"
+                );
+                self.notifier.notify(receipt_notification(
+                    NotifyEvent::TxReverted,
+                    action,
+                    round,
+                    &receipt,
+                ));
+                Err(AppError::Chain(
+                    ErrorKind::Revert,
+                    format!(
+                        "{action} tx reverted: round={} tx_hash={:?} block={:?} gas_used={:?}",
+                        round, receipt.transaction_hash, receipt.block_number, receipt.gas_used
+                    ),
+                ))
+            }
+            Ok(Ok(Some(receipt))) => {
+                info!(
+                    event = "tx_confirmed",
+                    action,
+                    round = %round,
+                    tx_hash = ?receipt.transaction_hash,
+                    status = "confirmed",
+                    block = receipt.block_number.map(|b| b.as_u64()),
+                    gas_used = receipt.gas_used.map(|g| g.low_u64()),
+                    "{action} tx confirmed"
+                );
+                self.notifier.notify(receipt_notification(
+                    NotifyEvent::TxConfirmed,
+                    action,
+                    round,
+                    &receipt,
+                ));
+                Ok(())
+            }
+            Ok(Ok(None)) => Err(AppError::Chain(
+                ErrorKind::Transport,
+                format!("{action} receipt missing (None): round={round} tx_hash={tx_hash:?}"),
+            )),
+            Ok(Err(e)) => Err(AppError::provider(
+                &format!("{action} receipt for tx_hash={tx_hash:?}"),
+                &e,
+            )),
+            Err(_) => Err(AppError::Chain(
+                ErrorKind::Transport,
+                format!(
+                    "{action} receipt timeout after {receipt_timeout_secs}s: round={round} tx_hash={tx_hash:?}"
+                ),
+            )),
+        }
+    }
+}
+
+fn receipt_notification(
+    event: NotifyEvent,
+    action: &str,
+    round: U256,
+    receipt: &TransactionReceipt,
+) -> Notification {
+    let what = match event {
+        NotifyEvent::TxReverted => "reverted",
+        _ => "confirmed",
+    };
+    let mut n = Notification::new(event, format!("{action} tx {what}"))
+        .field("action", action)
+        .field("round", round)
+        .field("tx_hash", format!("{:?}", receipt.transaction_hash));
+    if let Some(block) = receipt.block_number {
+        n = n.field("block", block);
+    }
+    if let Some(gas_used) = receipt.gas_used {
+        n = n.field("gas_used", gas_used);
+    }
+    n
+}
+
+/// One send-and-confirm cycle (or node request), exported when OTLP is on.
+fn tx_span(action: &str, round: U256) -> Span {
+    info_span!(
+        "tx",
+        action,
+        round = %round,
+        tx_hash = field::Empty,
+        gas_used = field::Empty,
+        block = field::Empty,
+        status = field::Empty,
+        error = field::Empty
+    )
+}
+
+fn record_result(span: &Span, res: &Result<(), AppError>) {
+    match res {
+        Ok(()) => span.record("status", "confirmed"),
+        Err(AppError::Interrupted(e)) => span
+            .record("status", "interrupted")
+            .record("error", e.as_str()),
+        Err(e) => span
+            .record("status", field::display(e.kind()))
+            .record("error", field::display(e)),
+    };
+}
+
+enum InflightState {
+    Mined {
+        status: Option<U64>,
+        block: Option<U64>,
+    },
+    Pending,
+    Dropped,
+}
+
+/// Mined (with any status), still known to the node, or gone.
+async fn inflight_state<M: Middleware + 'static>(
+    client: &M,
+    tx_hash: TxHash,
+) -> Result<InflightState, AppError> {
+    let receipt = client
+        .get_transaction_receipt(tx_hash)
+        .await
+        .map_err(|e| AppError::middleware("eth_getTransactionReceipt", &e))?;
+    if let Some(r) = receipt {
+        return Ok(InflightState::Mined {
+            status: r.status,
+            block: r.block_number,
+        });
+    }
+    let tx = client
+        .get_transaction(tx_hash)
+        .await
+        .map_err(|e| AppError::middleware("eth_getTransactionByHash", &e))?;
+    Ok(if tx.is_some() {
+        InflightState::Pending
+    } else {
+        InflightState::Dropped
+    })
+}
+
+fn receipt_status(status: Option<U64>) -> &'static str {
+    match status {
+        Some(s) if s.is_zero() => "reverted",
+        Some(_) => "confirmed",
+        None => "unknown",
+    }
+}
+
+/// Nonce for the next tx from the signer, counting txs still pending from other senders that
+/// share the key. The middleware default uses the latest block, which may reuse a nonce that is
+/// already taken. `None` if the client has no default sender.
+async fn pending_nonce<M: Middleware + 'static>(client: &M) -> Result<Option<U256>, AppError> {
+    let Some(from) = client.default_sender() else {
+        return Ok(None);
+    };
+    client
+        .get_transaction_count(from, Some(BlockNumber::Pending.into()))
+        .await
+        .map(Some)
+        .map_err(|e| AppError::middleware("eth_getTransactionCount(pending)", &e))
+}